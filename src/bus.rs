@@ -1,11 +1,14 @@
 use crate::config::AUDIO_SAMPLES;
 use crate::constants::{LCD_BYTE_WIDTH, LCD_HEIGHT};
+#[cfg(test)]
+use crate::constants::LCD_WIDTH;
 use crate::joypad::Joypad;
 use crate::apu::Apu;
-use crate::ppu::Ppu;
+use crate::ppu::{OamCorruption, Ppu};
 use crate::timer::Timer;
 use crate::cartridge::Cartridge;
-use crate::cpu::{GBModel, Interrupt};
+use crate::cpu::{Access, CodeDataLog, Exception, ExceptionKind, Exceptions, Fetch, GBModel, Interrupt, MemoryBus, Quirk, WatchHit, Watchpoints};
+use crate::cpu::{register_name, EventKind, EventLog, CDL_DATA, CDL_DMA};
 
 const WRAM_SIZE: usize = 0x1000;
 const HRAM_SIZE: usize = 0x0080;
@@ -28,7 +31,11 @@ const HRAM_START: usize = 0xFF80;
 const HRAM_END: usize = 0xFFFE;
 
 const DMA_M_CYCLES: u16 = 160;
-const HDMA_BLOCK_SIZE: usize = 0x10;
+const HDMA_BLOCK_SIZE: u16 = 0x10;
+
+// VRAM DMA copies 2 bytes per M-cycle in normal speed, so a block
+// stalls the CPU for 8 M-cycles (or 16 M-cycles in double speed).
+const HDMA_BLOCK_DOTS: u32 = 32;
 
 enum HDMAMode {
     GDMA,
@@ -40,6 +47,12 @@ pub struct Bus {
     model: GBModel,
     double_speed: bool,
     serial_output: String,
+    entered_hblank: bool,
+    ly_stub: bool,
+    watchpoints: Option<Watchpoints>,
+    exceptions: Option<Exceptions>,
+    code_data_log: Option<CodeDataLog>,
+    event_log: Option<EventLog>,
 
     cartridge: Cartridge,
     joypad: Joypad,
@@ -55,16 +68,13 @@ pub struct Bus {
 
     // CGB ONLY
     key1: u8,
-    hdma1: usize,
-    hdma2: usize,
-    hdma3: usize,
-    hdma4: usize,
-    hdma5: u8,
+    hdma_source: u16,
+    hdma_dest: u16,
     rp: u8,
     svbk: u8,
-    hdma_bytes: usize,
     hdma_mode: HDMAMode,
     hdma_length: u8,
+    hdma_pending: bool,
 }
 
 impl Bus {
@@ -73,6 +83,12 @@ impl Bus {
             model,
             double_speed: false,
             serial_output: String::new(),
+            entered_hblank: false,
+            ly_stub: false,
+            watchpoints: None,
+            exceptions: None,
+            code_data_log: None,
+            event_log: None,
 
             cartridge,
             joypad: Joypad::new(),
@@ -87,21 +103,19 @@ impl Bus {
             dma_ticks: DMA_M_CYCLES,
 
             key1: 0,
-            hdma1: 0,
-            hdma2: 0,
-            hdma3: 0,
-            hdma4: 0,
-            hdma5: 0xFF,
+            hdma_source: 0,
+            hdma_dest: 0x8000,
             rp: 0,
             svbk: 0,
-            hdma_bytes: 0,
             hdma_mode: HDMAMode::None,
-            hdma_length: 0,
+            hdma_length: 0x7F,
+            hdma_pending: false,
         }
     }
 
-    /// Steps through components that require M-cycle level accuracy;
-    /// this should also be called AFTER and BETWEEN (right after reads/writes) instructions.
+    /// Steps through all components over the given period (in T-cycles), which 
+    /// should be called for EVERY M-cycle of an instruction (right after reads/writes).
+    /// Updates interrupt flags accordingly.
     /// NOTE: This stepping is affected by double speed mode on CGB
     pub fn partial_step(&mut self, t_cycles: u32) {
         self.step_oam_dma(t_cycles / 4);
@@ -120,24 +134,41 @@ impl Bus {
                 self.apu.frame_sequencer_step();
             }
         }
-    }
 
-    /// Steps through other components to be done at the END OF EACH INTSTRUCTION.
-    /// Updates interrupt flags accordingly.
-    pub fn step(&mut self, t_cycles: u32) {
-        let mut t_cycles = t_cycles;
+        let dots = if self.double_speed { t_cycles / 2 } else { t_cycles };
+        self.step_components(dots);
+    }
 
-        if self.double_speed {
-            t_cycles /= 2;
-        }
+    /// Clears state tracked over a single instruction; called at the START OF EACH INSTRUCTION.
+    pub fn begin_step(&mut self) {
+        self.entered_hblank = false;
+    }
 
-        t_cycles += self.step_vram_dma();
+    /// Runs any pending VRAM DMA, to be done at the END OF EACH INTSTRUCTION;
+    /// returns the number of T-cycles the CPU was stalled by VRAM DMA for.
+    pub fn step(&mut self, cpu_halted: bool) -> u32 {
+        self.step_vram_dma(cpu_halted)
+    }
 
-        self.apu.step(t_cycles);
+    /// Steps the PPU and APU over the given period (in dots), requesting any interrupts triggered.
+    fn step_components(&mut self, dots: u32) {
+        self.apu.step(dots);
         
-        self.ppu.step(t_cycles);
+        self.ppu.step(dots);
+        if let Some(log) = &mut self.event_log {
+            if self.ppu.lcd_enabled() {
+                let (line, dot) = self.ppu.dot_position();
+                log.advance(line, dot, self.ppu.mode());
+            } else {
+                log.advance_lcd_off(dots);
+            }
+        }
+        if self.ppu.entered_hblank() {
+            self.entered_hblank = true;
+            self.hdma_pending |= matches!(self.hdma_mode, HDMAMode::HDMA);
+        }
 
-        if self.ppu.entered_vblank() {
+        if self.ppu.vblank_triggered() {
             self.request_interrupt(Interrupt::VBlank);
         }
         if self.ppu.stat_triggered() {
@@ -148,8 +179,42 @@ impl Bus {
         }
     }
 
-    /// Returns byte from specified address; returns 0xFF for unused addresses.
+    /// Returns byte from specified address (as read by the CPU); returns 0xFF for unused addresses.
     pub fn read_byte(&self, addr: u16) -> u8 {
+        self.cpu_read(addr, CDL_DATA)
+    }
+
+    /// Returns byte of an instruction from specified address (as fetched by the CPU).
+    pub fn fetch_byte(&self, addr: u16, fetch: Fetch) -> u8 {
+        self.cpu_read(addr, fetch.cdl_flag())
+    }
+
+    /// Reads a byte for the CPU; the byte is logged with cdl_flag if it is in ROM.
+    fn cpu_read(&self, addr: u16, cdl_flag: u8) -> u8 {
+        let byte = self.peek_byte(addr);
+        if let Some(watchpoints) = &self.watchpoints {
+            watchpoints.check(Access::Read, addr, byte);
+        }
+        if let Some(exceptions) = &self.exceptions {
+            self.check_exceptions(exceptions, Access::Read, addr);
+        }
+        self.log_rom_read(addr, cdl_flag);
+        byte
+    }
+
+    /// Marks the ROM byte at addr with flag in the code/data log (if enabled).
+    fn log_rom_read(&self, addr: u16, flag: u8) {
+        if let Some(log) = &self.code_data_log {
+            if addr as usize <= ROM_END {
+                if let Some(offset) = self.cartridge.rom_offset(addr as usize) {
+                    log.mark(offset, flag);
+                }
+            }
+        }
+    }
+
+    /// Returns byte from specified address, without it counting as a CPU access (e.g. for DMA).
+    pub fn peek_byte(&self, addr: u16) -> u8 {
         let addr = addr as usize;
 
         match addr {
@@ -167,6 +232,7 @@ impl Bus {
             0xFF0F          => self.interrupt_flag,
             0xFF10..=0xFF26 => self.apu.read_io(addr),
             0xFF30..=0xFF3F => self.apu.read_io(addr),
+            0xFF44 if self.ly_stub => 0x90,
             0xFF40..=0xFF4B => self.ppu.read_io(addr),
             0xFF50          => self.cartridge.read_bank(),
 
@@ -188,10 +254,28 @@ impl Bus {
 
     /// If specified address is writable, writes byte to it; MAY trigger an OAM DMA.
     pub fn write_byte(&mut self, addr: u16, byte: u8) {
+        if let Some(watchpoints) = &self.watchpoints {
+            watchpoints.check(Access::Write, addr, byte);
+        }
+        if let Some(exceptions) = &self.exceptions {
+            self.check_exceptions(exceptions, Access::Write, addr);
+        }
+        if self.exceptions.is_some() {
+            self.mark_written(addr);
+        }
+        if self.event_log.is_some() && register_name(addr).is_some() {
+            self.log_event(EventKind::RegisterWrite { addr, byte });
+        }
+        let rom_bank = self.rom_bank();
         let addr = addr as usize;
 
         match addr {
-            ROM_START..=ROM_END     => self.cartridge.write_rom(addr, byte),
+            ROM_START..=ROM_END     => {
+                self.cartridge.write_rom(addr, byte);
+                if self.rom_bank() != rom_bank {
+                    self.log_event(EventKind::BankSwitch { bank: self.rom_bank() });
+                }
+            },
             VRAM_START..=VRAM_END   => self.ppu.write_vram(addr, byte),
             RAM_START..=RAM_END     => self.cartridge.write_ram(addr, byte),
             WRAM_START..=WRAM_END   => self.write_wram(addr, byte),
@@ -213,10 +297,10 @@ impl Bus {
             // CGB Registers
             0xFF4D if self.is_cgb() => self.key1 = byte & 0x7F,
             0xFF4F if self.is_cgb() => self.ppu.write_io(addr, byte),
-            0xFF51 if self.is_cgb() => self.hdma1 = byte as usize,
-            0xFF52 if self.is_cgb() => self.hdma2 = byte as usize,
-            0xFF53 if self.is_cgb() => self.hdma3 = byte as usize,
-            0xFF54 if self.is_cgb() => self.hdma4 = byte as usize,
+            0xFF51 if self.is_cgb() => self.hdma_source = (self.hdma_source & 0x00FF) | ((byte as u16) << 8),
+            0xFF52 if self.is_cgb() => self.hdma_source = (self.hdma_source & 0xFF00) | (byte as u16 & 0xF0),
+            0xFF53 if self.is_cgb() => self.hdma_dest = 0x8000 | (self.hdma_dest & 0x00FF) | ((byte as u16 & 0x1F) << 8),
+            0xFF54 if self.is_cgb() => self.hdma_dest = (self.hdma_dest & 0xFF00) | (byte as u16 & 0xF0),
             0xFF55 if self.is_cgb() => self.write_hdma5(byte),
             0xFF56 if self.is_cgb() => self.rp = byte & 0xFD,
             0xFF68..=0xFF6C if self.is_cgb() => self.ppu.write_io(addr, byte),
@@ -228,6 +312,50 @@ impl Bus {
         }
     }
 
+    /// Raises any exception caused by a CPU access (see Exceptions).
+    fn check_exceptions(&self, exceptions: &Exceptions, access: Access, addr: u16) {
+        let kind = match addr as usize {
+            ROM_START..=ROM_END if access == Access::Write && !self.cartridge.is_mbc_register(addr as usize) => {
+                ExceptionKind::RomWrite
+            }
+            VRAM_START..=VRAM_END if !self.ppu.can_access_vram() => ExceptionKind::VramAccess,
+            OAM_START..=OAM_END if !self.ppu.can_access_oam() || self.dma_ticks < DMA_M_CYCLES => {
+                ExceptionKind::OamAccess
+            }
+            EMPTY_START..=EMPTY_END => ExceptionKind::EmptyAccess,
+            WRAM_START..=WRAM2_END if access == Access::Read && !exceptions.wram_written(self.wram_index(addr)) => {
+                ExceptionKind::UninitializedRead
+            }
+            HRAM_START..=HRAM_END if access == Access::Read && !exceptions.hram_written(addr as usize - HRAM_START) => {
+                ExceptionKind::UninitializedRead
+            }
+            _ => return,
+        };
+        exceptions.raise(kind, addr);
+    }
+
+    /// Marks WRAM/HRAM as initialized by a CPU write.
+    fn mark_written(&mut self, addr: u16) {
+        let index = self.wram_index(addr);
+        if let Some(exceptions) = &mut self.exceptions {
+            match addr as usize {
+                WRAM_START..=WRAM2_END => exceptions.set_wram_written(index),
+                HRAM_START..=HRAM_END => exceptions.set_hram_written(addr as usize - HRAM_START),
+                _ => {}
+            }
+        }
+    }
+
+    /// Returns the index of a WRAM address (or its echo) into all WRAM banks, for the current bank.
+    fn wram_index(&self, addr: u16) -> usize {
+        let offset = (addr as usize).wrapping_sub(WRAM_START) % (2 * WRAM_SIZE);
+        if offset < WRAM_SIZE || !self.is_cgb() {
+            return offset;
+        }
+        let wram_bank = ((self.svbk as usize) & 0x7) + (self.svbk == 0) as usize;
+        wram_bank * WRAM_SIZE + offset - WRAM_SIZE
+    }
+
     fn read_wram(&self, addr: usize) -> u8 {
         if addr < WRAM_START + WRAM_SIZE {
             return self.wram[0][addr - WRAM_START];
@@ -255,31 +383,51 @@ impl Bus {
         }
     }
 
+    /// Records event at the PPU's current position in the event log (if enabled).
+    fn log_event(&mut self, event: EventKind) {
+        if let Some(log) = &mut self.event_log {
+            let (line, dot) = self.ppu.dot_position();
+            log.record(line, dot, event);
+        }
+    }
+
     /// Writes to DMA register and initializes an OAM DMA transfer.
     fn write_dma(&mut self, byte: u8) {
+        self.log_event(EventKind::OamDma { source: (byte as u16) << 8 });
         self.ppu.write_dma(byte);
         self.dma_start = (byte as u16) << 8;
         self.dma_ticks = 0;
         self.step_oam_dma(1);
     }
 
-    /// Writes to HDMA5 register and initializes HDMA transfer
+    /// Writes to HDMA5 register, which starts a GDMA or HDMA transfer,
+    /// or cancels the currently running HDMA transfer.
     fn write_hdma5(&mut self, byte: u8) {
-        self.hdma5 = byte;
-        self.hdma_length = byte & 0x7F;
-        self.hdma_bytes = 0;
-               
         if byte & 0x80 == 0 {
             if matches!(self.hdma_mode, HDMAMode::HDMA) {
+                // remaining length is still readable from HDMA5 after cancelling
                 self.hdma_mode = HDMAMode::None;
+                self.hdma_pending = false;
             } else {
                 self.hdma_mode = HDMAMode::GDMA;
+                self.hdma_length = byte & 0x7F;
+                self.log_vram_dma(false);
             }
         } else {
             self.hdma_mode = HDMAMode::HDMA;
+            self.hdma_length = byte & 0x7F;
+            self.log_vram_dma(true);
+
+            // first block is copied right away if LCD is off or HBlank has already started
+            self.hdma_pending = !self.ppu.lcd_enabled() || self.ppu.in_hblank();
         }
     }
 
+    fn log_vram_dma(&mut self, hblank: bool) {
+        let length = (self.hdma_length as u16 + 1) * HDMA_BLOCK_SIZE;
+        self.log_event(EventKind::VramDma { source: self.hdma_source, dest: self.hdma_dest, length, hblank });
+    }
+
     /// Steps through a DMA transfer from 0xNN00-0xNN9F to 0xFE00-0xFE9F (OAM) 
     /// which runs for 160 M-cycles in total.
     fn step_oam_dma(&mut self, m_cycles: u32) {
@@ -288,7 +436,8 @@ impl Bus {
 
             // One byte transferred per M cycle during OAM DMA.\
             let dma_index = self.dma_ticks;
-            let byte = self.read_byte(self.dma_start | dma_index);
+            let byte = self.peek_byte(self.dma_start | dma_index);
+            self.log_rom_read(self.dma_start | dma_index, CDL_DMA);
             self.ppu.write_oam(0xFE00 | dma_index as usize, byte);
 
             m_cycles -= 1;
@@ -296,47 +445,27 @@ impl Bus {
         }
     }
 
-    /// (CGB Only) Steps through HDMA, returning the number of T-Cycles taken.
-    fn step_vram_dma(&mut self) -> u32 {
+    /// (CGB Only) Steps through VRAM DMA, returning the number of T-Cycles the CPU is stalled for.
+    /// HDMA blocks are not copied while the CPU is halted, and resume once it wakes up.
+    fn step_vram_dma(&mut self, cpu_halted: bool) -> u32 {
         if !self.is_cgb() {
             return 0;
         }
 
         match self.hdma_mode {
-            HDMAMode::GDMA => self.step_vram_gdma(),
-            HDMAMode::HDMA => self.step_vram_hdma(),
-            HDMAMode::None => 0
-        }
-    }
-
-    fn step_vram_gdma(&mut self) -> u32 {
-        let mut t_cycles = 0;
-        for _ in 0..self.hdma_transfer_blocks() {
-            t_cycles += self.transfer_block_to_vram();
-        }
-
-        self.hdma_length = 0x7F;
-        self.hdma_mode = HDMAMode::None;
-        
-        t_cycles
-    }
-
-    /// If HDMA is running, transfers a block of bytes to VRAM at each HBlank.
-    fn step_vram_hdma(&mut self) -> u32 {
-        if !self.ppu.entered_hblank() {
-            return 0;
-        }
-
-        let t_cycles = self.transfer_block_to_vram();
-
-        if self.hdma_bytes == self.hdma_transfer_blocks() * HDMA_BLOCK_SIZE {
-            self.hdma_mode = HDMAMode::None;
-            self.hdma_length = 0x7F;
-        } else {
-            self.hdma_length -= 1;
+            HDMAMode::GDMA => {
+                let mut t_cycles = 0;
+                while matches!(self.hdma_mode, HDMAMode::GDMA) {
+                    t_cycles += self.transfer_block_to_vram();
+                }
+                t_cycles
+            }
+            HDMAMode::HDMA if self.hdma_pending && !cpu_halted => {
+                self.hdma_pending = false;
+                self.transfer_block_to_vram()
+            }
+            _ => 0
         }
-
-        t_cycles
     }
 
     fn read_hdma5(&self) -> u8 {
@@ -348,34 +477,55 @@ impl Bus {
         status | self.hdma_length
     }
 
-    /// Does a DMA transfer of a block (0x10) of bytes to VRAM, returning the number of T-cycles taken.
+    /// Copies a block (0x10) of bytes to VRAM and steps through the time taken to copy it;
+    /// returns the number of T-cycles taken.
     fn transfer_block_to_vram(&mut self) -> u32 {
-        let source_start = self.hdma_source_start();
-        let dest_start = self.hdma_dest_start();
+        for _ in 0..HDMA_BLOCK_SIZE {
+            let byte = self.read_hdma_source(self.hdma_source);
+            self.log_rom_read(self.hdma_source, CDL_DMA);
+            self.ppu.write_vram(self.hdma_dest as usize, byte);
+
+            // destination wraps around within VRAM; source wraps around the address space 
+            self.hdma_source = self.hdma_source.wrapping_add(1);
+            self.hdma_dest = VRAM_START as u16 | (self.hdma_dest.wrapping_add(1) & 0x1FFF);
+        }
 
-        for i in  0..HDMA_BLOCK_SIZE {
-            let byte = self.read_byte((source_start + self.hdma_bytes + i) as u16);
-            self.ppu.write_vram(dest_start + self.hdma_bytes + i, byte);
+        if self.hdma_length == 0 {
+            self.hdma_mode = HDMAMode::None;
+            self.hdma_length = 0x7F;
+        } else {
+            self.hdma_length -= 1;
         }
-        self.hdma_bytes += HDMA_BLOCK_SIZE;
 
-        return 32;
-    }
+        // CPU is stalled during the copy, but components around it keep running
+        let t_cycles = if self.double_speed { HDMA_BLOCK_DOTS * 2 } else { HDMA_BLOCK_DOTS };
+        self.partial_step(t_cycles);
 
-    fn hdma_transfer_blocks(&self) -> usize {
-       (self.hdma5 as usize & 0x7F) + 1
+        t_cycles
     }
 
-    fn hdma_source_start(&self) -> usize {
-        (((self.hdma1 as usize) << 8)| self.hdma2 as usize) & 0xFFF0
+    /// Reads a byte for VRAM DMA; VRAM can't be used as a source
+    /// and 0xE000-0xFFFF reads from external RAM instead.
+    fn read_hdma_source(&self, addr: u16) -> u8 {
+        match addr as usize {
+            VRAM_START..=VRAM_END => 0xFF,
+            WRAM2_START..=0xFFFF => self.peek_byte(addr - 0x4000),
+            _ => self.peek_byte(addr),
+        }
     }
 
-    fn hdma_dest_start(&self) -> usize {
-        0x8000 | ((((self.hdma3 as usize) << 8)| self.hdma4 as usize) & 0x1FF0)
+    /// (DMG ONLY) Triggers the OAM corruption bug if addr is in 0xFE00-0xFEFF;
+    /// only has an effect while the PPU is in mode 2.
+    pub fn trigger_oam_bug(&mut self, addr: u16, corruption: OamCorruption) {
+        if self.is_cgb() || !(OAM_START..=EMPTY_END).contains(&(addr as usize)) {
+            return;
+        }
+        self.ppu.corrupt_oam(corruption);
     }
 
     /// Sets given interrupt's bit in IF, which requests for that interrupt.
     pub fn request_interrupt(&mut self, interrupt: Interrupt) {  
+        self.log_event(EventKind::InterruptRequest(interrupt));
         match interrupt {
             Interrupt::VBlank => self.interrupt_flag |= 1 << 0,
             Interrupt::Stat   => self.interrupt_flag |= 1 << 1,
@@ -410,8 +560,72 @@ impl Bus {
         self.ppu.get_display_output()
     }
 
+    /// Returns the PPU, e.g. for the VRAM viewer (see Ppu::render_tile_sheet).
+    pub fn ppu(&self) -> &Ppu {
+        &self.ppu
+    }
+
+    pub fn ppu_mut(&mut self) -> &mut Ppu {
+        &mut self.ppu
+    }
+
+    #[cfg(test)]
+    pub fn get_index_output(&self) -> &[u16; LCD_WIDTH * LCD_HEIGHT] {
+        self.ppu.get_index_output()
+    }
+
+    pub fn set_ly_stub(&mut self, enabled: bool) {
+        self.ly_stub = enabled;
+    }
+
+    /// Checks every CPU read and write against the given watchpoints (None to stop checking).
+    pub fn set_watchpoints(&mut self, watchpoints: Option<Watchpoints>) {
+        self.watchpoints = watchpoints;
+    }
+
+    /// Enables (or disables) exception reporting (see Exceptions).
+    pub fn set_exceptions(&mut self, enabled: bool) {
+        self.exceptions = if enabled { Some(Exceptions::new()) } else { None };
+    }
+
+    /// Returns (and clears) the exceptions raised since the last call, after checking the CPU's
+    /// state after an instruction at pc (with the resulting sp).
+    pub fn take_exceptions(&mut self, pc: u16, sp: u16) -> Vec<Exception> {
+        match &mut self.exceptions {
+            Some(exceptions) => {
+                exceptions.check_cpu(pc, sp);
+                exceptions.take()
+            }
+            None => Vec::new(),
+        }
+    }
+
+    /// Logs ROM reads into the given code/data log (None to stop logging).
+    pub fn set_code_data_log(&mut self, log: Option<CodeDataLog>) {
+        self.code_data_log = log;
+    }
+
+    pub fn code_data_log(&self) -> Option<&CodeDataLog> {
+        self.code_data_log.as_ref()
+    }
+
+    /// Records events into the given event log (None to stop recording).
+    pub fn set_event_log(&mut self, log: Option<EventLog>) {
+        self.event_log = log;
+    }
+
+    pub fn event_log(&self) -> Option<&EventLog> {
+        self.event_log.as_ref()
+    }
+
+    /// Returns (and clears) the first watchpoint hit since the last call.
+    pub fn take_watch_hit(&self) -> Option<WatchHit> {
+        self.watchpoints.as_ref().and_then(|watchpoints| watchpoints.take_hit())
+    }
+
+    /// Returns true if the PPU entered HBlank during the last instruction.
     pub fn entered_hblank(&self) -> bool {
-        self.ppu.entered_hblank()
+        self.entered_hblank
     }
 
     pub fn update_joypad(&mut self, status: u8) {
@@ -423,6 +637,10 @@ impl Bus {
         &self.serial_output
     }
 
+    pub fn rom_bank(&self) -> usize {
+        self.cartridge.rom_bank()
+    }
+
     pub fn save_mbc_state(&mut self) {
         self.cartridge.save_mbc_state()
     }
@@ -436,4 +654,53 @@ impl Bus {
     pub fn save_id(&self) -> Option<String> {
         self.cartridge.save_id()
     }
-}
\ No newline at end of file
+}
+
+impl MemoryBus for Bus {
+    fn read_byte(&self, addr: u16) -> u8 {
+        Bus::read_byte(self, addr)
+    }
+
+    fn write_byte(&mut self, addr: u16, byte: u8) {
+        Bus::write_byte(self, addr, byte)
+    }
+
+    fn fetch_byte(&self, addr: u16, fetch: Fetch) -> u8 {
+        Bus::fetch_byte(self, addr, fetch)
+    }
+
+    fn interrupt_dispatched(&mut self, interrupt: Interrupt) {
+        self.interrupt_flag &= !interrupt.mask();
+        self.log_event(EventKind::InterruptDispatch(interrupt));
+    }
+
+    fn peek_byte(&self, addr: u16) -> u8 {
+        Bus::peek_byte(self, addr)
+    }
+
+    fn rom_bank(&self) -> usize {
+        Bus::rom_bank(self)
+    }
+
+    fn tick(&mut self, t_cycles: u32) {
+        self.partial_step(t_cycles)
+    }
+
+    fn begin_step(&mut self) {
+        Bus::begin_step(self)
+    }
+
+    fn end_step(&mut self, cpu_halted: bool) -> u32 {
+        self.step(cpu_halted)
+    }
+
+    fn hardware_quirk(&mut self, quirk: Quirk) -> bool {
+        match quirk {
+            Quirk::OamBug(addr, corruption) => {
+                self.trigger_oam_bug(addr, corruption);
+                false
+            }
+            Quirk::Stop => self.speed_switch(),
+        }
+    }
+}
@@ -24,6 +24,8 @@ pub struct Header {
     global_checksum: u16,
 
     title: String,
+    // bytes 0x134-0x143 as read by the CGB boot ROM (see colourization_key)
+    title_bytes: [u8; 16],
     cartridge_type: u8,
     rom_size: u8,
     ram_size: u8, 
@@ -59,9 +61,11 @@ impl Header {
             // byte at 0x143 is used for CGB flag instead of title in this case
             title_end -= 1; 
         }
-        let title_bytes = header_bytes[0x34..=title_end].to_vec();
-        let title = match String::from_utf8(title_bytes) {
-            Ok(s) => s.replace("\0", ""),
+        // the title is padded with NULs (and may be followed by the manufacturer code)
+        let title_bytes = &header_bytes[0x34..=title_end];
+        let title_len = title_bytes.iter().position(|byte| *byte == 0).unwrap_or(title_bytes.len());
+        let title = match String::from_utf8(title_bytes[..title_len].to_vec()) {
+            Ok(s) => s,
             Err(e) => panic!("Unable to parse header title: {}", e),
         };
 
@@ -103,6 +107,7 @@ impl Header {
         Header {
             nintendo_logo,
             title,
+            title_bytes: header_bytes[0x34..=0x43].try_into().unwrap(),
             manufacturer_code,
             cgb_flag,
             licensee_code,
@@ -152,6 +157,18 @@ impl Header {
         self.cartridge_type
     }
 
+    /// Returns the sum of the title's bytes and its 4th letter, which the CGB boot ROM uses to colourize
+    /// DMG games; None if the game is not licensed by Nintendo (which the boot ROM does not look up).
+    pub fn colourization_key(&self) -> Option<(u8, u8)> {
+        // old licensee code 0x01, or new licensee code "01"
+        if self.licensee_code != 0x01 && self.licensee_code != 0x3031 {
+            return None;
+        }
+
+        let checksum = self.title_bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+        Some((checksum, self.title_bytes[3]))
+    }
+
     pub fn cgb_compatible(&self) -> bool {
         self.cgb_flag & 0x80 !=  0
     }
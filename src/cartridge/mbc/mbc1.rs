@@ -56,20 +56,8 @@ impl Mbc1 {
 
 impl Mbc for Mbc1 {
     fn read_rom(&self, addr: usize) -> u8 {
-        match addr {
-            0x0000..=0x3FFF => {
-                let rom_bank = if self.banking_mode {
-                    self.current_rom_bank & 0b1100000
-                } else {
-                    0
-                };
-                self.rom[rom_bank][addr]
-            },
-            0x4000..=0x7FFF => {
-                self.rom[self.current_rom_bank][addr - 0x4000]
-            }
-            _ => unreachable!()
-        }
+        let offset = self.rom_offset(addr);
+        self.rom[offset / ROM_BANK_SIZE][offset % ROM_BANK_SIZE]
     }
 
     fn write_rom(&mut self, addr: usize, byte: u8) {
@@ -123,6 +111,28 @@ impl Mbc for Mbc1 {
         };
     }
 
+    fn rom_bank(&self) -> usize {
+        self.current_rom_bank
+    }
+
+    fn rom_offset(&self, addr: usize) -> usize {
+        match addr {
+            0x0000..=0x3FFF => {
+                let rom_bank = if self.banking_mode {
+                    self.current_rom_bank & 0b1100000
+                } else {
+                    0
+                };
+                rom_bank * ROM_BANK_SIZE + addr
+            },
+            _ => self.current_rom_bank * ROM_BANK_SIZE + addr - 0x4000,
+        }
+    }
+
+    fn is_register(&self, addr: usize) -> bool {
+        addr <= 0x7FFF
+    }
+
     fn display(&self) -> String {
         let mut ret = format!("Mbc1 w/ {} ROM banks", self.rom_banks);
         if self.ram.is_some() {
@@ -43,13 +43,8 @@ impl Mbc2 {
 
 impl Mbc for Mbc2 {
     fn read_rom(&self, addr: usize) -> u8 {
-        match addr {
-            0x0000..=0x3FFF => self.rom[0][addr],
-            0x4000..=0x7FFF => {
-                self.rom[self.current_rom_bank][addr - 0x4000]
-            }
-            _ => unreachable!()
-        }
+        let offset = self.rom_offset(addr);
+        self.rom[offset / ROM_BANK_SIZE][offset % ROM_BANK_SIZE]
     }
 
     fn write_rom(&mut self, addr: usize, byte: u8) {
@@ -81,6 +76,21 @@ impl Mbc for Mbc2 {
         self.ram[(addr - RAM_START) & 0b111111111] = byte & 0xF;
     }
 
+    fn rom_bank(&self) -> usize {
+        self.current_rom_bank
+    }
+
+    fn rom_offset(&self, addr: usize) -> usize {
+        match addr {
+            0x0000..=0x3FFF => addr,
+            _ => self.current_rom_bank * ROM_BANK_SIZE + addr - 0x4000,
+        }
+    }
+
+    fn is_register(&self, addr: usize) -> bool {
+        addr <= 0x3FFF
+    }
+
     fn display(&self) -> String {
         let mut ret = format!("Mbc2 w/ {} ROM banks", self.rom_banks);
         if self.battery.is_some() {
@@ -34,6 +34,15 @@ pub trait Mbc {
     /// Handles bus reads from 0xA000 to 0xBFFF
     fn write_ram(&mut self, addr: usize, byte: u8);
 
+    /// Returns the ROM bank currently mapped to 0x4000-0x7FFF.
+    fn rom_bank(&self) -> usize;
+
+    /// Returns the offset into the ROM that a read of addr (in 0x0000-0x7FFF) comes from (see read_rom).
+    fn rom_offset(&self, addr: usize) -> usize;
+
+    /// Returns true if a write to addr (in 0x0000-0x7FFF) goes to an MBC register.
+    fn is_register(&self, addr: usize) -> bool;
+
     /// Displays Mbc specifications.
     fn display(&self) -> String;
 
@@ -9,7 +9,7 @@ pub struct NoMbc {
 
 impl Mbc for NoMbc {
     fn read_rom(&self, addr: usize) -> u8 {
-        self.rom[addr - ROM_START]
+        self.rom[self.rom_offset(addr)]
     }
 
     fn write_rom(&mut self, _addr: usize, _byte: u8) {
@@ -24,6 +24,18 @@ impl Mbc for NoMbc {
         self.ram[addr - RAM_START] = byte;
     }
 
+    fn rom_bank(&self) -> usize {
+        1
+    }
+
+    fn rom_offset(&self, addr: usize) -> usize {
+        addr - ROM_START
+    }
+
+    fn is_register(&self, _addr: usize) -> bool {
+        false
+    }
+
     fn display(&self) -> String {
         String::from("No Mbc")
     }
@@ -118,6 +118,11 @@ impl Cartridge {
         self.header.cgb_compatible()
     }
 
+    /// See Header::colourization_key.
+    pub fn colourization_key(&self) -> Option<(u8, u8)> {
+        self.header.colourization_key()
+    }
+
     pub fn get_title(&self) -> String {
         self.header.title()
     }
@@ -154,6 +159,33 @@ impl Cartridge {
         }
     }
 
+    /// Returns the ROM bank currently mapped to 0x4000-0x7FFF.
+    pub fn rom_bank(&self) -> usize {
+        self.mbc.rom_bank()
+    }
+
+    /// Returns the offset into the ROM that a read of addr (in 0x0000-0x7FFF) comes from,
+    /// or None if it comes from the boot ROM.
+    pub fn rom_offset(&self, addr: usize) -> Option<usize> {
+        let bootrom = self.bank == 0 && (addr < BOOTROM_SIZE
+            || self.cgb_bootrom && (BOOTROM_2_START..BOOTROM_2_END).contains(&addr));
+        if bootrom {
+            None
+        } else {
+            Some(self.mbc.rom_offset(addr))
+        }
+    }
+
+    /// Returns the size of the ROM in bytes (as given by its header).
+    pub fn rom_size(&self) -> usize {
+        self.header.num_rom_banks() * mbc::ROM_BANK_SIZE
+    }
+
+    /// Returns true if a write to addr (in 0x0000-0x7FFF) goes to an MBC register.
+    pub fn is_mbc_register(&self, addr: usize) -> bool {
+        self.mbc.is_register(addr)
+    }
+
     pub fn save_mbc_state(&self) {
         self.mbc.save_state();
     }
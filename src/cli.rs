@@ -0,0 +1,255 @@
+use crate::cpu::ExceptionMode;
+use crate::colourization::{palette_for_buttons, BUTTON_COMBINATIONS};
+use crate::palette::{parse_shades, ColourCorrection, DmgPalette, PaletteRegister, Shades, PRESETS};
+
+const ROM_PATH: &str = "roms/pokemoncrystal.gbc";
+const WITH_BOOTROM: bool = false;
+
+const USAGE: &str = "\
+usage: melon-gb [ROM] [options]
+       melon-gb disasm <ROM> [--bank <n>] [--from <addr>] [--count <n>]
+
+options:
+    --bootrom           run the boot ROM before the cartridge
+    --trace <file>      write a gameboy-doctor trace of every instruction to file
+    --annotate-trace    add the symbol file's labels and LD D,D debug messages to the trace
+                        as comment lines (; Label)
+    --compare <file>    stop at the first instruction that differs from a gameboy-doctor log
+    --ly-stub           make LY always read 0x90 (as gameboy-doctor logs expect)
+    --unlimited-sprites draw every sprite on a line, not just the first 10 (display only)
+    --palette <name>    DMG palette: green, pocket, light or contrast (F4 in the window cycles them)
+    --palette-bgp <colours>, --palette-obp0 <colours>, --palette-obp1 <colours>
+                        custom DMG colours for a palette register, as 4 RGB colours in hex,
+                        lightest first (e.g. E0F8D0,88C070,346856,081820)
+    --colour-correction <mode>
+                        how CGB colours are displayed: none, cgb (default), modern or gba
+    --brightness <n>    darken (down to -1.0) or lighten like a frontlight (up to 1.0) CGB colours
+    --colourize <mode>  colour DMG games as the CGB boot ROM does: by their title (auto), or with
+                        the palette of a button combination (e.g. up, left+a, down+b)
+    --debug             start paused in the debugger (press F12 in the window to pause again)
+    --debug-hooks       print LD D,D debug messages, and stop the debugger on LD B,B
+    --exceptions <mode> report suspicious memory accesses (e.g. reads of uninitialized RAM),
+                        either logging each one once (log) or stopping the debugger or GDB (break)
+    --gdb <port>        wait for GDB to connect on a local port (GDB remote serial protocol)
+    --cdl               log which ROM bytes are executed, read as data or copied by DMA,
+                        adding to the CDL file next to the ROM (e.g. game.cdl)
+    --events <file>     record interrupts, DMA, LCD register writes and bank switches by (line, dot),
+                        and write the last 60 frames to file as JSON
+    --events-overlay <file>
+                        draw the last frame's events over its PPU modes, as a 456x154 PNG
+    --vram-dump <dir>   save tile data, tile maps, OAM and palettes as PNGs into dir
+                        when exiting (F10 in the window saves them at any time)
+    --profile <file>    report where CPU time is spent per frame and when exiting,
+                        and write folded stacks (for flamegraphs) to file
+
+disasm options:
+    --bank <n>          ROM bank mapped to 0x4000-0x7FFF (default 1)
+    --from <addr>       address to start disassembling from (default 0x0100)
+    --count <n>         number of instructions to disassemble (default 32)
+
+numbers are decimal, or hexadecimal with a 0x prefix";
+
+/// What to do, parsed from command line arguments.
+pub enum Command {
+    Run(Box<Options>),
+    Disasm { rom_path: String, bank: usize, from: u16, count: usize },
+}
+
+impl Command {
+    pub fn parse(args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut args = args.peekable();
+        if args.peek().map(String::as_str) != Some("disasm") {
+            return Options::parse(args).map(|options| Command::Run(Box::new(options)));
+        }
+        args.next();
+
+        let mut rom_path = None;
+        let (mut bank, mut from, mut count) = (1, 0x0100, 32);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--bank" => bank = number(&value(&mut args, &arg)?)?,
+                "--from" => from = number(&value(&mut args, &arg)?)?,
+                "--count" => count = number(&value(&mut args, &arg)?)?,
+                "-h" | "--help" => return Err(String::from(USAGE)),
+                _ if arg.starts_with('-') => return Err(format!("unknown option {}\n\n{}", arg, USAGE)),
+                _ => rom_path = Some(arg),
+            }
+        }
+
+        let rom_path = rom_path.ok_or_else(|| format!("missing ROM for disasm\n\n{}", USAGE))?;
+        let from = u16::try_from(from).map_err(|_| format!("address {:#X} is out of range", from))?;
+        Ok(Command::Disasm { rom_path, bank, from, count })
+    }
+}
+
+/// Options for running the emulator, parsed from command line arguments.
+pub struct Options {
+    pub rom_path: String,
+    pub with_bootrom: bool,
+    pub trace: Option<String>,
+    pub annotate_trace: bool,
+    pub compare: Option<String>,
+    pub ly_stub: bool,
+    pub unlimited_sprites: bool,
+    pub palette: Option<String>,
+    pub palette_shades: Vec<(PaletteRegister, Shades)>,
+    pub colour_correction: Option<ColourCorrection>,
+    pub brightness: f32,
+    /// "auto", or a button combination
+    pub colourize: Option<String>,
+    pub debug: bool,
+    pub debug_hooks: bool,
+    pub gdb_port: Option<u16>,
+    pub exceptions: Option<ExceptionMode>,
+    pub profile: Option<String>,
+    pub cdl: bool,
+    pub events: Option<String>,
+    pub events_overlay: Option<String>,
+    pub vram_dump: Option<String>,
+}
+
+impl Options {
+    pub fn parse(args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut options = Options {
+            rom_path: String::from(ROM_PATH),
+            with_bootrom: WITH_BOOTROM,
+            trace: None,
+            annotate_trace: false,
+            compare: None,
+            ly_stub: false,
+            unlimited_sprites: false,
+            palette: None,
+            palette_shades: Vec::new(),
+            colour_correction: None,
+            brightness: 0.0,
+            colourize: None,
+            debug: false,
+            debug_hooks: false,
+            gdb_port: None,
+            exceptions: None,
+            profile: None,
+            cdl: false,
+            events: None,
+            events_overlay: None,
+            vram_dump: None,
+        };
+
+        let mut args = args.peekable();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--bootrom" => options.with_bootrom = true,
+                "--trace" => options.trace = Some(value(&mut args, &arg)?),
+                "--annotate-trace" => options.annotate_trace = true,
+                "--compare" => options.compare = Some(value(&mut args, &arg)?),
+                "--ly-stub" => options.ly_stub = true,
+                "--unlimited-sprites" => options.unlimited_sprites = true,
+                "--palette" => {
+                    let name = value(&mut args, &arg)?;
+                    if DmgPalette::preset(&name).is_none() {
+                        let presets: Vec<&str> = PRESETS.iter().map(|(preset, _)| *preset).collect();
+                        return Err(format!("unknown palette {} (expected {})", name, presets.join(", ")));
+                    }
+                    options.palette = Some(name);
+                }
+                "--palette-bgp" | "--palette-obp0" | "--palette-obp1" => {
+                    let register = PaletteRegister::parse(&arg["--palette-".len()..]).unwrap();
+                    let shades = parse_shades(&value(&mut args, &arg)?).map_err(|e| format!("{}: {}", arg, e))?;
+                    options.palette_shades.push((register, shades));
+                }
+                "--debug" => options.debug = true,
+                "--debug-hooks" => options.debug_hooks = true,
+                "--exceptions" => {
+                    options.exceptions = match value(&mut args, &arg)?.as_str() {
+                        "log" => Some(ExceptionMode::Log),
+                        "break" => Some(ExceptionMode::Break),
+                        mode => return Err(format!("invalid exception mode {} (expected log or break)", mode)),
+                    };
+                }
+                "--gdb" => {
+                    let port = value(&mut args, &arg)?;
+                    options.gdb_port = Some(port.parse().map_err(|_| format!("invalid port {}", port))?);
+                }
+                "--colour-correction" => {
+                    let mode = value(&mut args, &arg)?;
+                    let correction = ColourCorrection::parse(&mode).ok_or_else(|| {
+                        format!("invalid colour correction {} (expected {})", mode, ColourCorrection::NAMES.join(", "))
+                    })?;
+                    options.colour_correction = Some(correction);
+                }
+                "--brightness" => {
+                    let brightness = value(&mut args, &arg)?;
+                    options.brightness = match brightness.parse() {
+                        Ok(n) if (-1.0..=1.0).contains(&n) => n,
+                        _ => return Err(format!("invalid brightness {} (expected -1.0 to 1.0)", brightness)),
+                    };
+                }
+                "--colourize" => {
+                    let mode = value(&mut args, &arg)?.to_ascii_lowercase();
+                    if mode != "auto" && palette_for_buttons(&mode).is_none() {
+                        let buttons: Vec<&str> = BUTTON_COMBINATIONS.iter().map(|(buttons, _)| *buttons).collect();
+                        return Err(format!("invalid colourization {} (expected auto, or one of {})", mode, buttons.join(", ")));
+                    }
+                    options.colourize = Some(mode);
+                }
+                "--cdl" => options.cdl = true,
+                "--events" => options.events = Some(value(&mut args, &arg)?),
+                "--events-overlay" => options.events_overlay = Some(value(&mut args, &arg)?),
+                "--vram-dump" => options.vram_dump = Some(value(&mut args, &arg)?),
+                "--profile" => options.profile = Some(value(&mut args, &arg)?),
+                "-h" | "--help" => return Err(String::from(USAGE)),
+                _ if arg.starts_with('-') => return Err(format!("unknown option {}\n\n{}", arg, USAGE)),
+                _ => options.rom_path = arg,
+            }
+        }
+
+        if options.trace.is_some() && options.compare.is_some() {
+            return Err(String::from("--trace and --compare cannot be used together"));
+        }
+        if options.annotate_trace && options.trace.is_none() {
+            return Err(String::from("--annotate-trace needs --trace"));
+        }
+        if options.debug && options.gdb_port.is_some() {
+            return Err(String::from("--debug and --gdb cannot be used together"));
+        }
+        if matches!(options.exceptions, Some(ExceptionMode::Break)) && !options.debug && options.gdb_port.is_none() {
+            return Err(String::from("--exceptions break needs --debug or --gdb"));
+        }
+        if options.colourize.is_some() && (options.palette.is_some() || !options.palette_shades.is_empty()) {
+            return Err(String::from("--colourize cannot be used with --palette or custom palette colours"));
+        }
+
+        Ok(options)
+    }
+
+    /// Returns the DMG palette given by --palette and any custom colours, with its name
+    /// ("custom" if there are colours), or None if neither was given.
+    pub fn dmg_palette(&self) -> Option<(String, DmgPalette)> {
+        if self.palette.is_none() && self.palette_shades.is_empty() {
+            return None;
+        }
+
+        let mut palette = self.palette.as_deref().and_then(DmgPalette::preset).unwrap_or_default();
+        for (register, shades) in &self.palette_shades {
+            palette.set_shades(*register, *shades);
+        }
+        let name = match &self.palette {
+            Some(name) if self.palette_shades.is_empty() => name.clone(),
+            _ => String::from("custom"),
+        };
+        Some((name, palette))
+    }
+}
+
+/// Returns the value following the given option.
+fn value(args: &mut impl Iterator<Item = String>, option: &str) -> Result<String, String> {
+    args.next().ok_or_else(|| format!("missing value for {}", option))
+}
+
+/// Parses a decimal number, or a hexadecimal number with a 0x prefix.
+pub fn number(arg: &str) -> Result<usize, String> {
+    let parsed = match arg.strip_prefix("0x").or_else(|| arg.strip_prefix("0X")) {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => arg.parse(),
+    };
+    parsed.map_err(|_| format!("invalid number {}", arg))
+}
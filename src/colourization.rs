@@ -0,0 +1,203 @@
+use crate::palette::Rgb555Palette;
+
+/// Colours (RGB555) of the CGB boot ROM's compatibility palettes, 4 per palette (lightest first).
+const PALETTES: [u16; 30 * 4] = [
+    0x7FFF, 0x32BF, 0x00D0, 0x0000,
+    0x639F, 0x4279, 0x15B0, 0x04CB,
+    0x7FFF, 0x6E31, 0x454A, 0x0000,
+    0x7FFF, 0x1BEF, 0x0200, 0x0000,
+    0x7FFF, 0x421F, 0x1CF2, 0x0000,
+    0x7FFF, 0x5294, 0x294A, 0x0000,
+    0x7FFF, 0x03FF, 0x012F, 0x0000,
+    0x7FFF, 0x03EF, 0x01D6, 0x0000,
+    0x7FFF, 0x42B5, 0x3DC8, 0x0000,
+    0x7E74, 0x03FF, 0x0180, 0x0000,
+    0x67FF, 0x77AC, 0x1A13, 0x2D6B,
+    0x7ED6, 0x4BFF, 0x2175, 0x0000,
+    0x53FF, 0x4A5F, 0x7E52, 0x0000,
+    0x4FFF, 0x7ED2, 0x3A4C, 0x1CE0,
+    0x03ED, 0x7FFF, 0x255F, 0x0000,
+    0x036A, 0x021F, 0x03FF, 0x7FFF,
+    0x7FFF, 0x01DF, 0x0112, 0x0000,
+    0x231F, 0x035F, 0x00F2, 0x0009,
+    0x7FFF, 0x03EA, 0x011F, 0x0000,
+    0x299F, 0x001A, 0x000C, 0x0000,
+    0x7FFF, 0x027F, 0x001F, 0x0000,
+    0x7FFF, 0x03E0, 0x0206, 0x0120,
+    0x7FFF, 0x7EEB, 0x001F, 0x7C00,
+    0x7FFF, 0x3FFF, 0x7E00, 0x001F,
+    0x7FFF, 0x03FF, 0x001F, 0x0000,
+    0x03FF, 0x001F, 0x000C, 0x0000,
+    0x7FFF, 0x033F, 0x0193, 0x0000,
+    0x0000, 0x4200, 0x037F, 0x7FFF,
+    0x7FFF, 0x7E8C, 0x7C00, 0x0000,
+    0x7FFF, 0x1BEF, 0x6180, 0x0000,
+];
+
+/// Palette combinations: where the OBP0, OBP1 and BGP colours start in PALETTES (as colour offsets,
+/// which are not always at the start of a palette).
+const COMBINATIONS: [(usize, usize, usize); 51] = [
+    (4 * 4, 4 * 4, 29 * 4),
+    (18 * 4, 18 * 4, 18 * 4),
+    (20 * 4, 20 * 4, 20 * 4),
+    (24 * 4, 24 * 4, 24 * 4),
+    (9 * 4, 9 * 4, 9 * 4),
+    (0, 0, 0),
+    (27 * 4, 27 * 4, 27 * 4),
+    (5 * 4, 5 * 4, 5 * 4),
+    (12 * 4, 12 * 4, 12 * 4),
+    (26 * 4, 26 * 4, 26 * 4),
+    (16 * 4, 8 * 4, 8 * 4),
+    (4 * 4, 28 * 4, 28 * 4),
+    (4 * 4, 2 * 4, 2 * 4),
+    (3 * 4, 4 * 4, 4 * 4),
+    (4 * 4, 29 * 4, 29 * 4),
+    (28 * 4, 4 * 4, 28 * 4),
+    (2 * 4, 17 * 4, 2 * 4),
+    (16 * 4, 16 * 4, 8 * 4),
+    (4 * 4, 4 * 4, 7 * 4),
+    (4 * 4, 4 * 4, 18 * 4),
+    (4 * 4, 4 * 4, 20 * 4),
+    (19 * 4, 19 * 4, 9 * 4),
+    (4 * 4 - 1, 4 * 4 - 1, 11 * 4),
+    (17 * 4, 17 * 4, 2 * 4),
+    (4 * 4, 4 * 4, 2 * 4),
+    (4 * 4, 4 * 4, 3 * 4),
+    (28 * 4, 28 * 4, 0),
+    (3 * 4, 3 * 4, 0),
+    (0, 0, 4),
+    (18 * 4, 22 * 4, 18 * 4),
+    (20 * 4, 22 * 4, 20 * 4),
+    (24 * 4, 22 * 4, 24 * 4),
+    (16 * 4, 22 * 4, 8 * 4),
+    (17 * 4, 4 * 4, 13 * 4),
+    (28 * 4 - 1, 0, 14 * 4),
+    (28 * 4 - 1, 4 * 4, 15 * 4),
+    (19 * 4, 22 * 4, 9 * 4),
+    (16 * 4, 28 * 4, 10 * 4),
+    (4 * 4, 23 * 4, 28 * 4),
+    (17 * 4, 22 * 4, 2 * 4),
+    (4 * 4, 0, 2 * 4),
+    (4 * 4, 28 * 4, 3 * 4),
+    (28 * 4, 3 * 4, 0),
+    (3 * 4, 28 * 4, 4 * 4),
+    (21 * 4, 28 * 4, 4 * 4),
+    (3 * 4, 28 * 4, 0),
+    (25 * 4, 3 * 4, 28 * 4),
+    (0, 28 * 4, 8 * 4),
+    (4 * 4, 3 * 4, 28 * 4),
+    (28 * 4, 3 * 4, 6 * 4),
+    (4 * 4, 28 * 4, 29 * 4),
+];
+
+/// Title checksums of the games the boot ROM colourizes; checksums from index FIRST_AMBIGUOUS
+/// are shared by several games, which are told apart by the 4th letter of their title.
+const CHECKSUMS: [u8; 79] = [
+    0x00, 0x88, 0x16, 0x36, 0xD1, 0xDB, 0xF2, 0x3C, 0x8C, 0x92, 0x3D, 0x5C, 0x58, 0xC9, 0x3E, 0x70,
+    0x1D, 0x59, 0x69, 0x19, 0x35, 0xA8, 0x14, 0xAA, 0x75, 0x95, 0x99, 0x34, 0x6F, 0x15, 0xFF, 0x97,
+    0x4B, 0x90, 0x17, 0x10, 0x39, 0xF7, 0xF6, 0xA2, 0x49, 0x4E, 0x43, 0x68, 0xE0, 0x8B, 0xF0, 0xCE,
+    0x0C, 0x29, 0xE8, 0xB7, 0x86, 0x9A, 0x52, 0x01, 0x9D, 0x71, 0x9C, 0xBD, 0x5D, 0x6D, 0x67, 0x3F,
+    0x6B, 0xB3, 0x46, 0x28, 0xA5, 0xC6, 0xD3, 0x27, 0x61, 0x18, 0x66, 0x6A, 0xBF, 0x0D, 0xF4,
+];
+const FIRST_AMBIGUOUS: usize = 65;
+
+/// 4th title letters of the games with ambiguous checksums: letter i is for the checksum at
+/// FIRST_AMBIGUOUS + i % 14 (the number of ambiguous checksums).
+const LETTERS: &[u8; 29] = b"BEFAARBEKEK R-URAR INAILICE R";
+
+/// Combination (index into COMBINATIONS) for each checksum, then for each of LETTERS.
+const CHECKSUM_COMBINATIONS: [usize; 65 + 29] = [
+    0, 4, 5, 35, 34, 3, 31, 15, 10, 5, 19, 36, 7, 37, 30, 44,
+    21, 32, 31, 20, 5, 33, 13, 14, 5, 29, 5, 18, 9, 3, 2, 26,
+    25, 25, 41, 42, 26, 45, 42, 45, 36, 38, 26, 42, 30, 41, 34, 34,
+    5, 42, 6, 5, 33, 25, 42, 42, 40, 14, 16, 25, 5, 29, 5, 43,
+    6,
+    36, 22, 25, 6, 32, 12, 36, 11, 39, 18, 39, 24, 31, 50, 17, 46,
+    6, 27, 0, 47, 41, 41, 0, 0, 19, 34, 23, 18, 29,
+];
+
+/// Combinations selected by holding a direction (and A or B) while the boot ROM's logo is shown.
+pub const BUTTON_COMBINATIONS: [(&str, usize); 12] = [
+    ("up", 5),
+    ("up+a", 43),
+    ("up+b", 28),
+    ("left", 48),
+    ("left+a", 40),
+    ("left+b", 7),
+    ("down", 8),
+    ("down+a", 3),
+    ("down+b", 49),
+    ("right", 1),
+    ("right+a", 0),
+    ("right+b", 6),
+];
+
+/// Returns the palette the CGB boot ROM gives a DMG game, from its title checksum and the 4th letter
+/// of its title (see Cartridge::colourization_key); games not licensed by Nintendo (None) get the default.
+pub fn palette_for_game(key: Option<(u8, u8)>) -> Rgb555Palette {
+    let combination = key.and_then(|(checksum, letter)| {
+        let index = CHECKSUMS.iter().position(|c| *c == checksum)?;
+        if index < FIRST_AMBIGUOUS {
+            return Some(CHECKSUM_COMBINATIONS[index]);
+        }
+
+        let ambiguous = CHECKSUMS.len() - FIRST_AMBIGUOUS;
+        (index - FIRST_AMBIGUOUS..LETTERS.len()).step_by(ambiguous)
+            .find(|i| LETTERS[*i] == letter)
+            .map(|i| CHECKSUM_COMBINATIONS[FIRST_AMBIGUOUS + i])
+    });
+    combination_palette(combination.unwrap_or(0))
+}
+
+/// Returns the palette selected by a button combination (e.g. "left+b"), or None if there is none.
+pub fn palette_for_buttons(buttons: &str) -> Option<Rgb555Palette> {
+    let buttons = buttons.to_ascii_lowercase().replace(' ', "");
+    BUTTON_COMBINATIONS.iter()
+        .find(|(name, _)| *name == buttons)
+        .map(|(_, combination)| combination_palette(*combination))
+}
+
+fn combination_palette(combination: usize) -> Rgb555Palette {
+    let (obp0, obp1, bgp) = COMBINATIONS[combination];
+    let colours = |offset: usize| [0, 1, 2, 3].map(|i| PALETTES[offset + i]);
+    Rgb555Palette { bgp: colours(bgp), obp0: colours(obp0), obp1: colours(obp1) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{palette_for_buttons, palette_for_game, PALETTES};
+
+    #[test]
+    fn colourization_test() {
+        // POKEMON RED: red BG and OBP1, green OBP0
+        let red = palette_for_game(Some((0x14, b'E')));
+        assert_eq!(red.bgp[1], 0x421F);
+        assert_eq!(red.obp0[1], 0x1BEF);
+        assert_eq!(red.obp1, red.bgp);
+
+        // POKEMON BLUE shares its checksum with other games, and is told apart by its 4th letter
+        let blue = palette_for_game(Some((0x61, b'E')));
+        assert_eq!(blue.bgp[2], 0x7C00);
+        assert_eq!(blue.obp0[1], 0x421F);
+        assert_eq!(palette_for_game(Some((0x61, b'K'))), palette_for_game(None));
+
+        // SUPER MARIOLAND and METROID2 share a checksum too
+        let mario = palette_for_game(Some((0x46, b'E')));
+        assert_eq!(mario.bgp[..], PALETTES[11 * 4..12 * 4]);
+        assert_eq!(mario.obp0[2], 0x421F);
+        let metroid = palette_for_game(Some((0x46, b'R')));
+        assert_eq!(metroid.bgp[2], 0x7C00);
+        assert_ne!(metroid, mario);
+
+        // unknown games and games from other licensees get the default (same as right+a)
+        assert_eq!(palette_for_game(Some((0x02, b'A'))), palette_for_game(None));
+        assert_eq!(palette_for_buttons("right+a"), Some(palette_for_game(None)));
+
+        // left: blue BG, red OBP0, green OBP1
+        let left = palette_for_buttons("Left").unwrap();
+        assert_eq!(left.bgp[..], PALETTES[28 * 4..29 * 4]);
+        assert_eq!(left.obp0[1], 0x421F);
+        assert_eq!(left.obp1[1], 0x1BEF);
+        assert!(palette_for_buttons("select").is_none());
+    }
+}
@@ -1,10 +1,10 @@
 use crate::constants::BYTES_PER_PIXEL;
 
-// (CGB ONLY) set to true for display colours to be more 
-// realistic to actual hardware
-pub const WITH_COLOUR_CORRECTION: bool = true;
+// (CGB ONLY) colour correction used by default, for display colours to be more 
+// realistic to actual hardware: none, cgb, modern or gba (see palette.rs)
+pub const COLOUR_CORRECTION: &str = "cgb";
 
-///(DMG ONLY)
+///(DMG ONLY) colours of the "green" palette
 pub const COLOURS: [[u8; BYTES_PER_PIXEL]; 4] = [
     [0xE8, 0xFF, 0xFF, 0xFF], // => white
     [0x74, 0xD4, 0x9B, 0xFF], // => light grey
@@ -12,6 +12,9 @@ pub const COLOURS: [[u8; BYTES_PER_PIXEL]; 4] = [
     [0x4F, 0x3D, 0x1A, 0xFF], // => black
 ];
 
+///(DMG ONLY) palette used by default: green, pocket, light or contrast (see palette.rs)
+pub const DMG_PALETTE: &str = "green";
+
 pub const DMG_BOOTROM_PATH: &str = "bootroms/bootrom.gb";
 
 pub const CGB_BOOTROM_PATH: &str = "bootroms/bootrom.gbc";
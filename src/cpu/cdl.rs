@@ -0,0 +1,110 @@
+use std::cell::RefCell;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Flags logged per ROM byte (compatible with BizHawk's Game Boy CDL flags for the first three).
+pub const CDL_CODE: u8 = 0x01;
+pub const CDL_OPERAND: u8 = 0x02;
+pub const CDL_DATA: u8 = 0x04;
+/// Copied by OAM DMA or VRAM DMA.
+pub const CDL_DMA: u8 = 0x08;
+
+/// Kind of instruction byte fetched by the CPU.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Fetch {
+    /// First byte of an instruction
+    Opcode,
+    /// Any other byte of an instruction (including the second byte of CB-prefixed opcodes)
+    Operand,
+}
+
+impl Fetch {
+    pub fn cdl_flag(self) -> u8 {
+        match self {
+            Fetch::Opcode => CDL_CODE,
+            Fetch::Operand => CDL_OPERAND,
+        }
+    }
+}
+
+/// Code/data log: one byte of flags (CDL_*) per ROM byte, across all banks;
+/// marked by the bus on ROM reads while enabled.
+pub struct CodeDataLog {
+    flags: RefCell<Vec<u8>>,
+}
+
+impl CodeDataLog {
+    pub fn new(rom_size: usize) -> Self {
+        CodeDataLog { flags: RefCell::new(vec![0; rom_size]) }
+    }
+
+    /// Returns the CDL file next to the given ROM (e.g. game.cdl for game.gb).
+    pub fn path_for_rom(rom_path: &str) -> PathBuf {
+        Path::new(rom_path).with_extension("cdl")
+    }
+
+    /// Loads the log at path to add to, or makes a new one if there is no file
+    /// (or it is for a ROM of a different size).
+    pub fn load_or_new(path: &Path, rom_size: usize) -> io::Result<Self> {
+        match fs::read(path) {
+            Ok(flags) if flags.len() == rom_size => Ok(CodeDataLog { flags: RefCell::new(flags) }),
+            Ok(_) => {
+                println!("Ignoring {} (not for a ROM of {} bytes)", path.display(), rom_size);
+                Ok(CodeDataLog::new(rom_size))
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(CodeDataLog::new(rom_size)),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, &*self.flags.borrow())
+    }
+
+    /// Adds flag to the byte at offset into the ROM (offsets past the end are ignored).
+    pub fn mark(&self, offset: usize, flag: u8) {
+        if let Some(flags) = self.flags.borrow_mut().get_mut(offset) {
+            *flags |= flag;
+        }
+    }
+
+    #[cfg(test)]
+    pub fn flags(&self, offset: usize) -> u8 {
+        self.flags.borrow().get(offset).copied().unwrap_or(0)
+    }
+
+    /// Returns the number of ROM bytes with the given flag.
+    pub fn count(&self, flag: u8) -> usize {
+        self.flags.borrow().iter().filter(|flags| *flags & flag != 0).count()
+    }
+
+    /// Describes how much of the ROM has been logged, e.g. for printing when saving.
+    pub fn summary(&self) -> String {
+        let size = self.flags.borrow().len();
+        let logged = self.flags.borrow().iter().filter(|flags| **flags != 0).count();
+        format!("{} code, {} operand, {} data and {} DMA bytes; {} of {} bytes ({:.1}%) logged",
+            self.count(CDL_CODE), self.count(CDL_OPERAND), self.count(CDL_DATA), self.count(CDL_DMA),
+            logged, size, 100.0 * logged as f64 / size.max(1) as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CodeDataLog, CDL_CODE, CDL_DATA, CDL_OPERAND};
+
+    #[test]
+    fn cdl_test() {
+        let log = CodeDataLog::new(0x8000);
+        log.mark(0x0100, CDL_CODE);
+        log.mark(0x0101, CDL_OPERAND);
+        log.mark(0x0100, CDL_DATA);
+        log.mark(0x8000, CDL_DATA);
+
+        assert_eq!(log.flags(0x0100), CDL_CODE | CDL_DATA);
+        assert_eq!(log.flags(0x0101), CDL_OPERAND);
+        assert_eq!(log.flags(0x0102), 0);
+        assert_eq!(log.count(CDL_DATA), 1);
+        assert!(log.summary().ends_with("2 of 32768 bytes (0.0%) logged"));
+    }
+}
@@ -0,0 +1,134 @@
+use super::{Cpu, MemoryBus};
+
+// LD D,D is followed by JR over $64 $64 $00 $00 and the message text
+const MESSAGE_SIGNATURE: [u8; 4] = [0x64, 0x64, 0x00, 0x00];
+const JR_E8: u8 = 0x18;
+
+impl<B: MemoryBus> Cpu<B> {
+    /// Enables source-level debug hooks (as in BGB and no$gmb): LD B,B is a software breakpoint
+    /// (see take_debug_break), and LD D,D followed by a message sequence prints the message.
+    pub fn set_debug_hooks(&mut self, enabled: bool) {
+        self.debug_hooks = enabled;
+    }
+
+    /// Returns true (once) if an LD B,B breakpoint was executed since the last call.
+    pub fn take_debug_break(&mut self) -> bool {
+        std::mem::take(&mut self.debug_break)
+    }
+
+    /// Called on LD B,B.
+    pub(super) fn debug_break_hook(&mut self) {
+        if self.debug_hooks {
+            self.debug_break = true;
+        }
+    }
+
+    /// Called on LD D,D; prints the message if the instruction is followed by
+    /// the no$gmb message sequence: JR .end, dw $6464, dw $0000, db "message", .end
+    pub(super) fn debug_message_hook(&mut self) {
+        if !self.debug_hooks {
+            return;
+        }
+
+        let pc = self.pc.full();
+        let len = self.bus.peek_byte(pc.wrapping_add(1));
+        let signature: Vec<u8> = (2..6).map(|i| self.bus.peek_byte(pc.wrapping_add(i))).collect();
+        if self.bus.peek_byte(pc) != JR_E8 || !(4..=0x7F).contains(&len) || signature != MESSAGE_SIGNATURE {
+            return;
+        }
+
+        let text: String = (6..len as u16 + 2)
+            .map(|i| char::from(self.bus.peek_byte(pc.wrapping_add(i))))
+            .collect();
+        let message = self.expand_debug_message(&text);
+
+        println!("[debug] {}", message);
+        // only annotated traces take comments, so plain ones stay comparable with gameboy-doctor
+        if let Some(tracer) = &mut self.tracer {
+            tracer.comment(&message);
+        }
+    }
+
+    /// Replaces %EXPR% in a debug message with its value: registers (e.g. %A%, %HL%),
+    /// flags (%ZFLAG%, %NFLAG%, %HFLAG%, %CFLAG%), %IME% and %ROMBANK%;
+    /// unknown expressions are left as they are.
+    fn expand_debug_message(&self, text: &str) -> String {
+        let mut message = String::new();
+        let mut parts = text.split('%');
+        message.push_str(parts.next().unwrap_or(""));
+
+        // parts alternate between expressions and text, e.g. "A=", "A", " HL=", "HL", ""
+        while let Some(expression) = parts.next() {
+            match (self.debug_expression(expression), parts.next()) {
+                (Some(value), Some(rest)) => {
+                    message.push_str(&value);
+                    message.push_str(rest);
+                }
+                (_, rest) => {
+                    message.push('%');
+                    message.push_str(expression);
+                    if let Some(rest) = rest {
+                        message.push('%');
+                        message.push_str(rest);
+                    }
+                }
+            }
+        }
+
+        message
+    }
+
+    fn debug_expression(&self, expression: &str) -> Option<String> {
+        let value = match expression.to_uppercase().as_str() {
+            "A" => format!("${:02X}", self.af.hi()),
+            "F" => format!("${:02X}", self.af.lo()),
+            "B" => format!("${:02X}", self.bc.hi()),
+            "C" => format!("${:02X}", self.bc.lo()),
+            "D" => format!("${:02X}", self.de.hi()),
+            "E" => format!("${:02X}", self.de.lo()),
+            "H" => format!("${:02X}", self.hl.hi()),
+            "L" => format!("${:02X}", self.hl.lo()),
+            "AF" => format!("${:04X}", self.af.full()),
+            "BC" => format!("${:04X}", self.bc.full()),
+            "DE" => format!("${:04X}", self.de.full()),
+            "HL" => format!("${:04X}", self.hl.full()),
+            "SP" => format!("${:04X}", self.sp.full()),
+            // address of the LD D,D
+            "PC" => format!("${:04X}", self.pc.full().wrapping_sub(1)),
+            "ZFLAG" => (self.af.bit(7) as u8).to_string(),
+            "NFLAG" => (self.af.bit(6) as u8).to_string(),
+            "HFLAG" => (self.af.bit(5) as u8).to_string(),
+            "CFLAG" | "CARRY" => (self.af.bit(4) as u8).to_string(),
+            "IME" => (self.ime as u8).to_string(),
+            "ROMBANK" => format!("${:02X}", self.bus.rom_bank()),
+            _ => return None,
+        };
+        Some(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cpu::{Cpu, FlatBus, GBModel};
+
+    #[test]
+    fn debug_message_test() {
+        let mut bus = FlatBus::new();
+        bus.load(0x0100, &[0x52, 0x18, 0x0D, 0x64, 0x64, 0x00, 0x00]);
+        bus.load(0x0107, b"A=%A% %X%");
+        bus.load(0x0110, &[0x40]);
+
+        let mut cpu = Cpu::from_bus(bus, GBModel::DMG);
+        cpu.set_debug_hooks(true);
+        assert_eq!(cpu.expand_debug_message("A=%A% HL=%HL% %X% 50%"), "A=$01 HL=$014D %X% 50%");
+
+        cpu.step();
+        assert_eq!(cpu.registers().pc, 0x0101);
+        cpu.step();
+        assert_eq!(cpu.registers().pc, 0x0110);
+        assert!(!cpu.take_debug_break());
+        cpu.step();
+        assert!(cpu.take_debug_break());
+        assert!(!cpu.take_debug_break());
+    }
+}
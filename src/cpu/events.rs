@@ -0,0 +1,282 @@
+use std::collections::VecDeque;
+use std::fmt::Write;
+
+use super::Interrupt;
+
+/// Size of the dot grid of a frame: 456 dots per line, 154 lines (including VBlank).
+pub const FRAME_DOTS: usize = 456;
+pub const FRAME_LINES: usize = 154;
+
+// number of completed frames kept
+const MAX_FRAMES: usize = 60;
+
+// overlay colours (RGB) of the PPU modes, and of events by kind
+const MODE_COLOURS: [[u8; 3]; 4] = [[0x20, 0x28, 0x50], [0x30, 0x30, 0x30], [0x28, 0x50, 0x28], [0x60, 0x48, 0x20]];
+const LCD_OFF_COLOUR: [u8; 3] = [0x00, 0x00, 0x00];
+// events are drawn this many dots wide (so that they can be seen)
+const MARKER_DOTS: usize = 3;
+
+/// Something that happened at a point in a frame (see EventLog).
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EventKind {
+    InterruptRequest(Interrupt),
+    InterruptDispatch(Interrupt),
+    OamDma { source: u16 },
+    /// length is in bytes; hblank is false for general-purpose DMA
+    VramDma { source: u16, dest: u16, length: u16, hblank: bool },
+    RegisterWrite { addr: u16, byte: u8 },
+    BankSwitch { bank: usize },
+}
+
+impl EventKind {
+    fn colour(&self) -> [u8; 3] {
+        match self {
+            EventKind::InterruptRequest(_) => [0xFF, 0x40, 0x40],
+            EventKind::InterruptDispatch(_) => [0xFF, 0xE0, 0x40],
+            EventKind::OamDma { .. } | EventKind::VramDma { .. } => [0x40, 0xE0, 0xFF],
+            EventKind::RegisterWrite { .. } => [0xFF, 0xFF, 0xFF],
+            EventKind::BankSwitch { .. } => [0xFF, 0x40, 0xFF],
+        }
+    }
+
+    /// Returns the fields of the event as JSON (without braces).
+    fn json_fields(&self) -> String {
+        match self {
+            EventKind::InterruptRequest(interrupt) => format!(r#""type":"interrupt_request","interrupt":"{:?}""#, interrupt),
+            EventKind::InterruptDispatch(interrupt) => format!(r#""type":"interrupt_dispatch","interrupt":"{:?}""#, interrupt),
+            EventKind::OamDma { source } => format!(r#""type":"oam_dma","source":{}"#, source),
+            EventKind::VramDma { source, dest, length, hblank } => {
+                let mode = if *hblank { "hblank" } else { "general" };
+                format!(r#""type":"vram_dma","mode":"{}","source":{},"dest":{},"length":{}"#, mode, source, dest, length)
+            }
+            EventKind::RegisterWrite { addr, byte } => {
+                let name = register_name(*addr).unwrap_or("?");
+                format!(r#""type":"register_write","register":"{}","addr":{},"value":{}"#, name, addr, byte)
+            }
+            EventKind::BankSwitch { bank } => format!(r#""type":"bank_switch","bank":{}"#, bank),
+        }
+    }
+}
+
+/// Returns the name of an IO register whose writes are logged, or None if they are not.
+pub fn register_name(addr: u16) -> Option<&'static str> {
+    let name = match addr {
+        0xFF40 => "LCDC",
+        0xFF41 => "STAT",
+        0xFF42 => "SCY",
+        0xFF43 => "SCX",
+        0xFF47 => "BGP",
+        0xFF48 => "OBP0",
+        0xFF49 => "OBP1",
+        0xFF4A => "WY",
+        0xFF4B => "WX",
+        0xFF68 => "BCPS",
+        0xFF69 => "BCPD",
+        0xFF6A => "OCPS",
+        0xFF6B => "OCPD",
+        _ => return None,
+    };
+    Some(name)
+}
+
+/// An event, at the (line, dot) of the frame it happened in.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Event {
+    pub line: u8,
+    pub dot: u16,
+    pub kind: EventKind,
+}
+
+/// The events of one frame.
+#[derive(Default)]
+pub struct FrameEvents {
+    pub number: u64,
+    pub events: Vec<Event>,
+    // PPU mode at each dot (only if modes are recorded), or 0xFF while the LCD is off
+    modes: Vec<u8>,
+}
+
+/// Records events (interrupts, DMA, writes to LCD registers and bank switches) by where
+/// in the frame they happened, keeping the last MAX_FRAMES frames; logged by the bus while enabled.
+/// While the LCD is off, events are at (0, 0), and frames end every FRAME_DOTS * FRAME_LINES dots.
+pub struct EventLog {
+    frames: VecDeque<FrameEvents>,
+    current: FrameEvents,
+    record_modes: bool,
+    // last position the PPU was stepped to, as an index into the dot grid
+    last_index: usize,
+    // dots since the LCD was turned off (or since the last frame ended while it is off)
+    lcd_off_dots: Option<usize>,
+}
+
+impl EventLog {
+    /// Makes an event log; record_modes also records the PPU mode at every dot (for render_overlay).
+    pub fn new(record_modes: bool) -> Self {
+        EventLog {
+            frames: VecDeque::new(),
+            current: EventLog::new_frame(0, record_modes),
+            record_modes,
+            last_index: 0,
+            lcd_off_dots: None,
+        }
+    }
+
+    fn new_frame(number: u64, record_modes: bool) -> FrameEvents {
+        let modes = if record_modes { vec![0xFF; FRAME_DOTS * FRAME_LINES] } else { Vec::new() };
+        FrameEvents { number, events: Vec::new(), modes }
+    }
+
+    pub fn record(&mut self, line: u8, dot: u16, kind: EventKind) {
+        self.current.events.push(Event { line, dot, kind });
+    }
+
+    /// Called after the PPU is stepped to (line, dot) in the given mode with the LCD on;
+    /// starts a new frame when the position wraps back to the start.
+    pub fn advance(&mut self, line: u8, dot: u16, mode: u8) {
+        self.lcd_off_dots = None;
+        let index = grid_index(line, dot);
+        if index < self.last_index {
+            self.fill_modes(self.last_index, FRAME_DOTS * FRAME_LINES, mode);
+            self.end_frame();
+        }
+
+        self.fill_modes(self.last_index, index + 1, mode);
+        self.last_index = index;
+    }
+
+    /// Called after the PPU is stepped over the given number of dots with the LCD off;
+    /// ends the frame when the LCD is turned off, and then every FRAME_DOTS * FRAME_LINES dots.
+    pub fn advance_lcd_off(&mut self, dots: u32) {
+        let mut elapsed = match self.lcd_off_dots {
+            Some(elapsed) => elapsed,
+            None => {
+                self.end_frame();
+                0
+            }
+        };
+
+        elapsed += dots as usize;
+        if elapsed >= FRAME_DOTS * FRAME_LINES {
+            elapsed -= FRAME_DOTS * FRAME_LINES;
+            self.end_frame();
+        }
+        self.lcd_off_dots = Some(elapsed);
+    }
+
+    fn end_frame(&mut self) {
+        let next = EventLog::new_frame(self.current.number + 1, self.record_modes);
+        self.frames.push_back(std::mem::replace(&mut self.current, next));
+        if self.frames.len() > MAX_FRAMES {
+            self.frames.pop_front();
+        }
+        self.last_index = 0;
+    }
+
+    fn fill_modes(&mut self, start: usize, end: usize, mode: u8) {
+        if let Some(modes) = self.current.modes.get_mut(start..end) {
+            modes.fill(mode);
+        }
+    }
+
+    /// Returns the completed frames kept, oldest first.
+    pub fn frames(&self) -> impl Iterator<Item = &FrameEvents> {
+        self.frames.iter()
+    }
+
+    /// Returns the completed frames as JSON: {"frames": [{"frame": n, "events": [...]}]},
+    /// where each event has its line, dot, type and the type's fields.
+    pub fn to_json(&self) -> String {
+        let mut json = String::from("{\"frames\":[");
+        for (i, frame) in self.frames.iter().enumerate() {
+            if i > 0 {
+                json.push(',');
+            }
+            write!(json, "\n{{\"frame\":{},\"events\":[", frame.number).unwrap();
+            for (j, event) in frame.events.iter().enumerate() {
+                if j > 0 {
+                    json.push(',');
+                }
+                write!(json, "\n{{\"line\":{},\"dot\":{},{}}}", event.line, event.dot, event.kind.json_fields()).unwrap();
+            }
+            json.push_str("]}");
+        }
+        json.push_str("\n]}\n");
+        json
+    }
+
+    /// Renders the last completed frame as an RGB image of the FRAME_DOTS x FRAME_LINES dot grid:
+    /// each dot coloured by PPU mode (if recorded), with events drawn over it; None if no frame has completed.
+    pub fn render_overlay(&self) -> Option<Vec<u8>> {
+        let frame = self.frames.back()?;
+        let mut rgb = Vec::with_capacity(FRAME_DOTS * FRAME_LINES * 3);
+        for i in 0..FRAME_DOTS * FRAME_LINES {
+            let colour = match frame.modes.get(i) {
+                Some(&mode) if mode < 4 => MODE_COLOURS[mode as usize],
+                _ => LCD_OFF_COLOUR,
+            };
+            rgb.extend_from_slice(&colour);
+        }
+
+        for event in &frame.events {
+            let line = (event.line as usize).min(FRAME_LINES - 1);
+            for dot in event.dot as usize..(event.dot as usize + MARKER_DOTS).min(FRAME_DOTS) {
+                let i = (line * FRAME_DOTS + dot) * 3;
+                rgb[i..i + 3].copy_from_slice(&event.kind.colour());
+            }
+        }
+        Some(rgb)
+    }
+}
+
+fn grid_index(line: u8, dot: u16) -> usize {
+    (line as usize * FRAME_DOTS + dot as usize).min(FRAME_DOTS * FRAME_LINES - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EventKind, EventLog, FRAME_DOTS, FRAME_LINES};
+    use crate::cpu::Interrupt;
+
+    #[test]
+    fn event_log_test() {
+        let mut log = EventLog::new(true);
+        log.advance(0, 80, 2);
+        log.record(0, 100, EventKind::RegisterWrite { addr: 0xFF43, byte: 0x12 });
+        log.advance(144, 4, 1);
+        log.record(144, 4, EventKind::InterruptRequest(Interrupt::VBlank));
+        assert!(log.render_overlay().is_none());
+
+        log.advance(0, 4, 2);
+        log.record(0, 8, EventKind::BankSwitch { bank: 3 });
+        assert_eq!(log.frames().count(), 1);
+
+        let json: serde_json::Value = serde_json::from_str(&log.to_json()).unwrap();
+        let events = &json["frames"][0]["events"];
+        assert_eq!(json["frames"][0]["frame"], 0);
+        assert_eq!(events.as_array().unwrap().len(), 2);
+        assert_eq!(events[0]["register"], "SCX");
+        assert_eq!(events[0]["value"], 0x12);
+        assert_eq!(events[1]["line"], 144);
+        assert_eq!(events[1]["interrupt"], "VBlank");
+
+        let rgb = log.render_overlay().unwrap();
+        assert_eq!(rgb.len(), FRAME_DOTS * FRAME_LINES * 3);
+        assert_eq!(rgb[100 * 3..100 * 3 + 3], [0xFF, 0xFF, 0xFF]);
+
+        // turning the LCD off ends the frame, and frames keep ending while it stays off
+        log.advance(0, 40, 2);
+        log.advance_lcd_off(4);
+        assert_eq!(log.frames().count(), 2);
+        log.record(0, 0, EventKind::RegisterWrite { addr: 0xFF40, byte: 0x00 });
+        log.advance_lcd_off((FRAME_DOTS * FRAME_LINES) as u32);
+        assert_eq!(log.frames().count(), 3);
+        assert_eq!(log.frames().last().unwrap().events.len(), 1);
+        log.advance_lcd_off(4);
+        assert_eq!(log.frames().count(), 3);
+
+        // turning it back on starts from (0, 0) of the current frame
+        log.advance(0, 4, 2);
+        log.advance(0, 8, 2);
+        assert_eq!(log.frames().count(), 3);
+    }
+}
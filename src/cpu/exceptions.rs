@@ -0,0 +1,139 @@
+use std::cell::RefCell;
+
+const WRAM_BYTES: usize = 8 * 0x1000;
+const HRAM_BYTES: usize = 0x7F;
+
+/// Kind of suspicious memory access or CPU state (as in BGB's exceptions).
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum ExceptionKind {
+    /// Read of WRAM/HRAM that has not been written since power on
+    UninitializedRead,
+    /// Write to ROM that is not an MBC register
+    RomWrite,
+    /// VRAM access while the PPU is drawing (mode 3)
+    VramAccess,
+    /// OAM access during OAM scan/drawing (modes 2 and 3), or OAM DMA
+    OamAccess,
+    /// Access to the unusable region 0xFEA0-0xFEFF
+    EmptyAccess,
+    /// Instruction fetched from outside ROM, WRAM and HRAM
+    Execute,
+    /// SP moved outside WRAM and HRAM
+    StackPointer,
+}
+
+/// How exceptions are reported by the emulator.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ExceptionMode {
+    /// Print each exception once per kind, address and PC
+    Log,
+    /// Print every exception and pause the debugger (or GDB)
+    Break,
+}
+
+/// An exception, at the address accessed (or PC for Execute, and SP for StackPointer).
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Exception {
+    pub kind: ExceptionKind,
+    pub addr: u16,
+}
+
+impl Exception {
+    pub fn describe(&self) -> String {
+        match self.kind {
+            ExceptionKind::UninitializedRead => format!("read of uninitialized RAM at {:04X}", self.addr),
+            ExceptionKind::RomWrite => format!("write to ROM at {:04X} (not an MBC register)", self.addr),
+            ExceptionKind::VramAccess => format!("VRAM access at {:04X} while inaccessible", self.addr),
+            ExceptionKind::OamAccess => format!("OAM access at {:04X} while inaccessible", self.addr),
+            ExceptionKind::EmptyAccess => format!("access to unusable memory at {:04X}", self.addr),
+            ExceptionKind::Execute => format!("executing from {:04X}", self.addr),
+            ExceptionKind::StackPointer => format!("SP moved outside WRAM/HRAM to {:04X}", self.addr),
+        }
+    }
+}
+
+/// Tracks what is needed to detect exceptions, and the exceptions raised by the bus
+/// since they were last taken; only kept by the bus while exceptions are enabled.
+pub struct Exceptions {
+    wram_written: Vec<bool>,
+    hram_written: [bool; HRAM_BYTES],
+    last_sp: u16,
+    raised: RefCell<Vec<Exception>>,
+}
+
+impl Default for Exceptions {
+    fn default() -> Self {
+        Exceptions::new()
+    }
+}
+
+impl Exceptions {
+    pub fn new() -> Self {
+        Exceptions {
+            wram_written: vec![false; WRAM_BYTES],
+            hram_written: [false; HRAM_BYTES],
+            last_sp: 0xFFFE,
+            raised: RefCell::new(Vec::new()),
+        }
+    }
+
+    pub fn raise(&self, kind: ExceptionKind, addr: u16) {
+        self.raised.borrow_mut().push(Exception { kind, addr });
+    }
+
+    /// Index is into all WRAM banks (bank * 0x1000 + offset).
+    pub fn wram_written(&self, index: usize) -> bool {
+        self.wram_written[index]
+    }
+
+    pub fn set_wram_written(&mut self, index: usize) {
+        self.wram_written[index] = true;
+    }
+
+    /// Index is from 0xFF80.
+    pub fn hram_written(&self, index: usize) -> bool {
+        self.hram_written[index]
+    }
+
+    pub fn set_hram_written(&mut self, index: usize) {
+        self.hram_written[index] = true;
+    }
+
+    /// Checks the CPU's state after an instruction at pc, with the resulting sp;
+    /// SP is only reported when it changes.
+    pub fn check_cpu(&mut self, pc: u16, sp: u16) {
+        if !matches!(pc, 0x0000..=0x7FFF | 0xC000..=0xDFFF | 0xFF80..=0xFFFE) {
+            self.raise(ExceptionKind::Execute, pc);
+        }
+
+        if sp != self.last_sp && !matches!(sp, 0xC000..=0xE000 | 0xFF80..=0xFFFF) {
+            self.raise(ExceptionKind::StackPointer, sp);
+        }
+        self.last_sp = sp;
+    }
+
+    /// Returns (and clears) the exceptions raised since the last call.
+    pub fn take(&self) -> Vec<Exception> {
+        self.raised.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Exception, ExceptionKind, Exceptions};
+
+    #[test]
+    fn exceptions_cpu_test() {
+        let mut exceptions = Exceptions::new();
+        exceptions.check_cpu(0x0150, 0xDFF0);
+        exceptions.check_cpu(0xFF90, 0xFFFE);
+        assert!(exceptions.take().is_empty());
+
+        exceptions.check_cpu(0x8000, 0xA000);
+        exceptions.check_cpu(0x0150, 0xA000);
+        assert_eq!(exceptions.take(), vec![
+            Exception { kind: ExceptionKind::Execute, addr: 0x8000 },
+            Exception { kind: ExceptionKind::StackPointer, addr: 0xA000 },
+        ]);
+    }
+}
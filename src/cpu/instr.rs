@@ -1,11 +1,23 @@
 #![allow(non_snake_case)]
-use super::{Cpu, GBModel, Interrupt::{self, *}};
-
-impl Cpu {
-    /// Execute the next instruction and steps through SOME parts bus (see partial_step in bus);
-    /// returns TOTAL number of M-cycles taken.
+use super::{Cpu, Fetch, GBModel, Interrupt::*, MemoryBus, Quirk};
+use crate::ppu::OamCorruption;
+
+// Operand names in the order they are encoded in opcodes, as taken by r8/r16 below
+// (the disassembler decodes with these); 8-bit register 6 is the byte at (HL) rather than a register.
+pub const R8_NAMES: [&str; 8] = ["B", "C", "D", "E", "H", "L", "(HL)", "A"];
+pub const R16_NAMES: [&str; 4] = ["BC", "DE", "HL", "SP"];
+pub const R16_STACK_NAMES: [&str; 4] = ["BC", "DE", "HL", "AF"];
+// Condition, ALU, accumulator and CB-prefixed operation names in the order they are encoded in opcodes.
+pub const CC_NAMES: [&str; 4] = ["NZ", "Z", "NC", "C"];
+pub const ALU_NAMES: [&str; 8] = ["ADD A,", "ADC A,", "SUB", "SBC A,", "AND", "XOR", "OR", "CP"];
+pub const ACC_NAMES: [&str; 8] = ["RLCA", "RRCA", "RLA", "RRA", "DAA", "CPL", "SCF", "CCF"];
+pub const CB_NAMES: [&str; 8] = ["RLC", "RRC", "RL", "RR", "SLA", "SRA", "SWAP", "SRL"];
+
+impl<B: MemoryBus> Cpu<B> {
+    /// Execute the next instruction, stepping through the bus (see partial_step in bus) on each 
+    /// memory access; returns TOTAL number of M-cycles taken (including ones not yet stepped through).
     pub(super) fn execute_next_instruction(&mut self) -> u32 {
-        let opcode = self.bus_read_byte(self.PC());
+        let opcode = self.bus_fetch_byte(self.PC(), Fetch::Opcode);
 
         if self.halt_bug {
             self.halt_bug = false;
@@ -82,7 +94,7 @@ impl Cpu {
             0x3E => self.ld_r8_n8("A"),
             0x3F => self.ccf(),
 
-            0x40 => self.ld_r8_r8("B", "B"),
+            0x40 => self.ld_b_b(),
             0x41 => self.ld_r8_r8("B", "C"),
             0x42 => self.ld_r8_r8("B", "D"),
             0x43 => self.ld_r8_r8("B", "E"),
@@ -101,7 +113,7 @@ impl Cpu {
 
             0x50 => self.ld_r8_r8("D", "B"),
             0x51 => self.ld_r8_r8("D", "C"),
-            0x52 => self.ld_r8_r8("D", "D"),
+            0x52 => self.ld_d_d(),
             0x53 => self.ld_r8_r8("D", "E"),
             0x54 => self.ld_r8_r8("D", "H"),
             0x55 => self.ld_r8_r8("D", "L"),
@@ -291,28 +303,48 @@ impl Cpu {
         m_cycles as u32
     }
 
-    /// does a JUMP to interrupt vector and resets IF bit, returning M-cycles taken.
-    pub(super) fn handle_interrupt(&mut self, interrupt: Interrupt) -> u32 {
-        self.push_stack(self.PC());
+    /// Dispatches the highest priority pending interrupt over 5 M-cycles, returning M-cycles taken.
+    /// The interrupt is re-sampled after PC's high byte is pushed, so if that push overwrites IE
+    /// and no interrupt is left pending, dispatch is cancelled and PC is set to 0x0000 instead.
+    pub(super) fn handle_interrupt(&mut self) -> u32 {
+        self.ime = false;
 
-        let bit = match interrupt {
-            VBlank => 0,
-            Stat => 1, 
-            Timer => 2,
-            Serial => 3,
-            Joypad => 4,
+        // after EI + HALT triggers the HALT bug, the interrupt returns to HALT itself
+        let pc = if self.halt_bug {
+            self.halt_bug = false;
+            self.PC().wrapping_sub(1)
+        } else {
+            self.PC()
         };
 
-        let interrupt_flag = self.bus.read_byte(0xFF0F);
-        self.bus.write_byte(0xFF0F,  interrupt_flag & !(1 << bit));
+        self.internal_cycle();
+        self.bus.hardware_quirk(Quirk::OamBug(self.SP(), OamCorruption::Write));
+        self.internal_cycle();
+
+        let sp = self.SP().wrapping_sub(1);
+        self.bus_write_byte(sp, (pc >> 8) as u8);
+        let interrupt = self.get_pending_interrupt();
+
+        let sp = sp.wrapping_sub(1);
+        self.bus_write_byte(sp, pc as u8);
+        self.set_SP(sp);
 
         let jump_vector = match interrupt {
-            VBlank => 0x40,
-            Stat => 0x48, 
-            Timer => 0x50,
-            Serial => 0x58,
-            Joypad => 0x60,
+            Some(interrupt) => {
+                self.bus.interrupt_dispatched(interrupt);
+
+                match interrupt {
+                    VBlank => 0x40,
+                    Stat => 0x48, 
+                    Timer => 0x50,
+                    Serial => 0x58,
+                    Joypad => 0x60,
+                }
+            }
+            None => 0x0000,
         };
+
+        self.internal_cycle();
         self.set_PC(jump_vector);
 
         5
@@ -326,7 +358,7 @@ impl Cpu {
     fn stop(&mut self) -> u8 {
         let _ = self.n8();
         if matches!(self.model, GBModel::CGB) {
-            if self.bus.speed_switch() {
+            if self.bus.hardware_quirk(Quirk::Stop) {
                 self.do_speed_switch = true;
             } else {
                 panic!("STOP called on CGB but no speed switch armed")
@@ -346,12 +378,18 @@ impl Cpu {
 
     fn di(&mut self) -> u8 {
         self.ime = false;
+        self.scheduled_ei = false;
         1
     }
 
+    /// If an interrupt is already pending, HALT exits immediately; with IME unset 
+    /// (or set by the EI right before), this triggers the HALT bug.
     fn halt(&mut self) -> u8 {
-        self.halt_triggered = true;
-        self.halted = true;
+        if self.get_pending_interrupt().is_none() {
+            self.halted = true;
+        } else if !self.ime {
+            self.halt_bug = true;
+        }
         1
     }
 
@@ -459,6 +497,9 @@ impl Cpu {
     }
 
     fn ret_cc(&mut self, cc: bool) -> u8 {
+        // condition is checked during an extra M-cycle before popping
+        self.internal_cycle();
+
         if cc { 
             let res = self.pop_stack();
             self.set_PC(res); 
@@ -491,7 +532,7 @@ impl Cpu {
 
     fn pop_stack(&mut self) -> u16 {
         let sp = self.SP();
-        let lo = self.bus_read_byte(sp) as u16;
+        let lo = self.bus_read_byte_inc(sp) as u16;
         let hi = self.bus_read_byte(sp.wrapping_add(1)) as u16;
         self.set_SP(sp.wrapping_add(2));
         (hi << 8) | lo as u16
@@ -501,6 +542,11 @@ impl Cpu {
         let hi = ((val16 & 0xFF00) >> 8) as u8;
         let lo = val16 as u8;
         let sp = self.SP();
+
+        // SP is decremented during an extra M-cycle before writing
+        self.bus.hardware_quirk(Quirk::OamBug(sp, OamCorruption::Write));
+        self.internal_cycle();
+
         self.bus_write_byte(sp.wrapping_sub(1), hi);
         self.bus_write_byte(sp.wrapping_sub(2), lo);
         self.set_SP(sp.wrapping_sub(2));
@@ -511,6 +557,18 @@ impl Cpu {
         1
     }
 
+    /// LD B,B; a software breakpoint if debug hooks are enabled.
+    fn ld_b_b(&mut self) -> u8 {
+        self.debug_break_hook();
+        1
+    }
+
+    /// LD D,D; may print a debug message if debug hooks are enabled.
+    fn ld_d_d(&mut self) -> u8 {
+        self.debug_message_hook();
+        1
+    }
+
     fn ld_r8_n8(&mut self, r8_name: &str) -> u8 {
         let n8 = self.n8();
         self.set_r8(r8_name, n8);
@@ -580,7 +638,7 @@ impl Cpu {
 
     fn ld_a_hli(&mut self) -> u8 {
         let hl = self.HL();
-        let byte = self.bus_read_byte(hl);
+        let byte = self.bus_read_byte_inc(hl);
         self.set_A(byte);
         self.set_HL(hl.wrapping_add(1));
         2
@@ -588,7 +646,7 @@ impl Cpu {
 
     fn ld_a_hld(&mut self) -> u8 {
         let hl = self.HL();
-        let byte = self.bus_read_byte(hl);
+        let byte = self.bus_read_byte_inc(hl);
         self.set_A(byte);
         self.set_HL(hl.wrapping_sub(1));
         2
@@ -693,6 +751,7 @@ impl Cpu {
 
     fn dec_r16(&mut self, r16_name: &str) -> u8 {
         let r16 = self.r16(r16_name) as u16;
+        self.bus.hardware_quirk(Quirk::OamBug(r16, OamCorruption::Write));
         self.set_r16(r16_name, r16.wrapping_sub(1));
         2
     }
@@ -715,6 +774,7 @@ impl Cpu {
 
     fn inc_r16(&mut self, r16_name: &str) -> u8 {
         let r16 = self.r16(r16_name) as u16;
+        self.bus.hardware_quirk(Quirk::OamBug(r16, OamCorruption::Write));
         self.set_r16(r16_name, r16.wrapping_add(1));
         2
     }
@@ -926,7 +986,7 @@ impl Cpu {
     }
 
     fn cb_execute(&mut self, ) -> u8 {
-        let opcode = self.bus_read_byte(self.PC());
+        let opcode = self.bus_fetch_byte(self.PC(), Fetch::Operand);
         self.inc_PC(1);
 
         match opcode {
@@ -1386,15 +1446,42 @@ impl Cpu {
     }
 
     fn bus_read_byte(&mut self, addr: u16) -> u8 {
+        self.bus.hardware_quirk(Quirk::OamBug(addr, OamCorruption::Read));
         let byte = self.bus.read_byte(addr);
-        self.bus.partial_step(4);
+        self.bus.tick(4);
+        self.t_cycles_so_far += 4;
+        byte
+    }
+
+    /// Same as bus_read_byte, but for bytes of the instruction (see MemoryBus::fetch_byte).
+    fn bus_fetch_byte(&mut self, addr: u16, fetch: Fetch) -> u8 {
+        self.bus.hardware_quirk(Quirk::OamBug(addr, OamCorruption::Read));
+        let byte = self.bus.fetch_byte(addr, fetch);
+        self.bus.tick(4);
         self.t_cycles_so_far += 4;
         byte
     }
 
+    /// Same as bus_read_byte, but for reads where the address register is
+    /// incremented/decremented in the same M-cycle (affects the OAM corruption bug).
+    fn bus_read_byte_inc(&mut self, addr: u16) -> u8 {
+        self.bus.hardware_quirk(Quirk::OamBug(addr, OamCorruption::ReadIncrease));
+        let byte = self.bus.read_byte(addr);
+        self.bus.tick(4);
+        self.t_cycles_so_far += 4;
+        byte
+    }
+
+    /// Does an M-cycle without accessing memory.
+    fn internal_cycle(&mut self) {
+        self.bus.tick(4);
+        self.t_cycles_so_far += 4;
+    }
+
     fn bus_write_byte(&mut self, addr: u16, byte: u8) {
+        self.bus.hardware_quirk(Quirk::OamBug(addr, OamCorruption::Write));
         self.bus.write_byte(addr, byte);
-        self.bus.partial_step(4);
+        self.bus.tick(4);
         self.t_cycles_so_far += 4;
     }
 
@@ -1409,7 +1496,7 @@ impl Cpu {
     }
 
     fn n8(&mut self) -> u8 {
-        let res = self.bus_read_byte(self.PC());
+        let res = self.bus_fetch_byte(self.PC(), Fetch::Operand);
         self.inc_PC(1);
         res
     }
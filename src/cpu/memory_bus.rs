@@ -0,0 +1,203 @@
+#[cfg(test)]
+use std::cell::RefCell;
+
+use crate::ppu::OamCorruption;
+
+use super::{Fetch, Interrupt};
+
+/// Everything the CPU is connected to, as seen by the CPU.
+/// NOTE: the CPU calls tick(4) after every M-cycle of an instruction (including ones
+/// that do not access memory), so reads and writes happen on the correct M-cycle.
+pub trait MemoryBus {
+    fn read_byte(&self, addr: u16) -> u8;
+
+    fn write_byte(&mut self, addr: u16, byte: u8);
+
+    /// Reads a byte of an instruction (at PC); only differs from read_byte for code/data logging.
+    fn fetch_byte(&self, addr: u16, _fetch: Fetch) -> u8 {
+        self.read_byte(addr)
+    }
+
+    /// Reads a byte for inspection (e.g. tracing or debugging), which is not a memory access by the CPU.
+    fn peek_byte(&self, addr: u16) -> u8 {
+        self.read_byte(addr)
+    }
+
+    /// Returns the ROM bank mapped to 0x4000-0x7FFF (e.g. for looking up labels).
+    fn rom_bank(&self) -> usize {
+        1
+    }
+
+    /// Steps through everything connected to the bus over the given period (in T-cycles).
+    fn tick(&mut self, t_cycles: u32);
+
+    /// Returns IE & IF; this is checked by the CPU between instructions, 
+    /// and is not a memory access by the CPU.
+    fn pending_interrupts(&self) -> u8 {
+        self.read_byte(0xFFFF) & self.read_byte(0xFF0F)
+    }
+
+    /// Called at the START OF EACH INSTRUCTION.
+    fn begin_step(&mut self) {}
+
+    /// Called at the END OF EACH INSTRUCTION; returns the number of T-cycles the CPU is stalled for.
+    fn end_step(&mut self, _cpu_halted: bool) -> u32 {
+        0
+    }
+
+    /// Called when the CPU dispatches an interrupt, which clears its bit in IF;
+    /// like pending_interrupts, this is not a memory access by the CPU.
+    fn interrupt_dispatched(&mut self, interrupt: Interrupt) {
+        let interrupt_flag = self.read_byte(0xFF0F);
+        self.write_byte(0xFF0F, interrupt_flag & !interrupt.mask());
+    }
+
+    /// Called for CPU actions that trigger quirks of the Game Boy's own bus;
+    /// returns true if a speed switch was done (see Quirk::Stop).
+    /// NOTE: Quirk is not exported from the library, so buses outside this crate
+    /// always use this default.
+    fn hardware_quirk(&mut self, _quirk: Quirk) -> bool {
+        false
+    }
+}
+
+/// CPU actions that trigger quirks of the Game Boy's own bus (see MemoryBus::hardware_quirk).
+pub enum Quirk {
+    /// (DMG only) An access to the given address that can trigger the OAM corruption bug.
+    OamBug(u16, OamCorruption),
+    /// (CGB only) STOP was executed; switches speed if a speed switch was armed.
+    Stop,
+}
+
+#[cfg(test)]
+/// 64 KiB of RAM with nothing else connected, e.g. for testing the CPU on its own.
+pub struct FlatBus {
+    ram: Vec<u8>,
+}
+
+#[cfg(test)]
+impl FlatBus {
+    pub fn new() -> Self {
+        FlatBus { ram: vec![0; 0x10000] }
+    }
+
+    /// Copies data into RAM starting at addr (wrapping around at 0xFFFF).
+    pub fn load(&mut self, addr: u16, data: &[u8]) {
+        for (i, byte) in data.iter().enumerate() {
+            self.ram[addr.wrapping_add(i as u16) as usize] = *byte;
+        }
+    }
+}
+
+#[cfg(test)]
+impl Default for FlatBus {
+    fn default() -> Self {
+        FlatBus::new()
+    }
+}
+
+#[cfg(test)]
+impl MemoryBus for FlatBus {
+    fn read_byte(&self, addr: u16) -> u8 {
+        self.ram[addr as usize]
+    }
+
+    fn write_byte(&mut self, addr: u16, byte: u8) {
+        self.ram[addr as usize] = byte;
+    }
+
+    fn tick(&mut self, _t_cycles: u32) {}
+}
+
+#[cfg(test)]
+/// A memory access made by the CPU, and the T-cycle it was made on.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BusAccess {
+    Read { cycle: u64, addr: u16, byte: u8 },
+    Write { cycle: u64, addr: u16, byte: u8 },
+}
+
+#[cfg(test)]
+/// Wraps another bus, recording every memory access made by the CPU (e.g. for tracing);
+/// clearing IF when an interrupt is dispatched is not recorded, as it is not one.
+pub struct RecordingBus<B> {
+    inner: B,
+    cycles: u64,
+    accesses: RefCell<Vec<BusAccess>>,
+}
+
+#[cfg(test)]
+impl<B: MemoryBus> RecordingBus<B> {
+    pub fn new(inner: B) -> Self {
+        RecordingBus { inner, cycles: 0, accesses: RefCell::new(Vec::new()) }
+    }
+
+    pub fn inner(&self) -> &B {
+        &self.inner
+    }
+
+    /// Returns the number of T-cycles ticked so far.
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// Returns (and clears) all accesses recorded so far.
+    pub fn take_accesses(&mut self) -> Vec<BusAccess> {
+        self.accesses.take()
+    }
+}
+
+#[cfg(test)]
+impl<B: MemoryBus> MemoryBus for RecordingBus<B> {
+    fn read_byte(&self, addr: u16) -> u8 {
+        let byte = self.inner.read_byte(addr);
+        self.accesses.borrow_mut().push(BusAccess::Read { cycle: self.cycles, addr, byte });
+        byte
+    }
+
+    fn write_byte(&mut self, addr: u16, byte: u8) {
+        self.inner.write_byte(addr, byte);
+        self.accesses.borrow_mut().push(BusAccess::Write { cycle: self.cycles, addr, byte });
+    }
+
+    fn fetch_byte(&self, addr: u16, fetch: Fetch) -> u8 {
+        let byte = self.inner.fetch_byte(addr, fetch);
+        self.accesses.borrow_mut().push(BusAccess::Read { cycle: self.cycles, addr, byte });
+        byte
+    }
+
+    fn tick(&mut self, t_cycles: u32) {
+        self.cycles += t_cycles as u64;
+        self.inner.tick(t_cycles);
+    }
+
+    fn peek_byte(&self, addr: u16) -> u8 {
+        self.inner.peek_byte(addr)
+    }
+
+    fn rom_bank(&self) -> usize {
+        self.inner.rom_bank()
+    }
+
+    fn pending_interrupts(&self) -> u8 {
+        self.inner.pending_interrupts()
+    }
+
+    fn begin_step(&mut self) {
+        self.inner.begin_step()
+    }
+
+    fn end_step(&mut self, cpu_halted: bool) -> u32 {
+        let stalled = self.inner.end_step(cpu_halted);
+        self.cycles += stalled as u64;
+        stalled
+    }
+
+    fn interrupt_dispatched(&mut self, interrupt: Interrupt) {
+        self.inner.interrupt_dispatched(interrupt)
+    }
+
+    fn hardware_quirk(&mut self, quirk: Quirk) -> bool {
+        self.inner.hardware_quirk(quirk)
+    }
+}
@@ -1,13 +1,35 @@
+mod cdl;
+mod debug_hooks;
+mod events;
+mod exceptions;
 mod instr;
+mod memory_bus;
 mod register;
+mod trace;
+mod watch;
+#[cfg(test)]
+mod mooneye;
+#[cfg(test)]
+mod sm83;
 
+pub use self::memory_bus::{MemoryBus, Quirk};
+#[cfg(test)]
+pub use self::memory_bus::{BusAccess, FlatBus, RecordingBus};
 use self::register::Register;
+pub use self::cdl::{CodeDataLog, Fetch, CDL_DATA, CDL_DMA};
+pub use self::events::{register_name, EventKind, EventLog, FRAME_DOTS, FRAME_LINES};
+pub use self::exceptions::{Exception, ExceptionKind, ExceptionMode, Exceptions};
+pub use self::trace::Tracer;
+pub use self::watch::{Access, WatchHit, Watchpoints};
+pub(crate) use self::instr::{ACC_NAMES, ALU_NAMES, CB_NAMES, CC_NAMES, R16_NAMES, R16_STACK_NAMES, R8_NAMES};
 use self::Interrupt::*;
 
 use crate::bus::Bus;
 use crate::cartridge::Cartridge;
 use crate::config::AUDIO_SAMPLES;
 use crate::constants::{LCD_BYTE_WIDTH, LCD_HEIGHT};
+#[cfg(test)]
+use crate::constants::LCD_WIDTH;
 
 #[derive(Clone, Copy, Debug)]
 pub enum GBModel {
@@ -15,15 +37,14 @@ pub enum GBModel {
     CGB
 }
 
-pub struct Cpu {
-    bus: Bus,
+pub struct Cpu<B = Bus> {
+    bus: B,
     model: GBModel,
 
     pub(self) scheduled_ei: bool,
     pub(self) ime: bool,
     pub(self) halted: bool,
     pub(self) halt_bug: bool,
-    pub(self) halt_triggered: bool,
     pub(self) t_cycles_so_far: u32,
 
     pub(self) af: Register,
@@ -33,10 +54,15 @@ pub struct Cpu {
     pub(self) pc: Register,
     pub(self) sp: Register,
 
+    tracer: Option<Tracer>,
+    debug_hooks: bool,
+    debug_break: bool,
+
     // CGB ONLY
     do_speed_switch: bool,
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum Interrupt {
     VBlank,
     Stat,
@@ -45,6 +71,19 @@ pub enum Interrupt {
     Joypad,
 }
 
+impl Interrupt {
+    /// Returns the interrupt's bit in IE and IF.
+    pub fn mask(self) -> u8 {
+        match self {
+            Interrupt::VBlank => 1 << 0,
+            Interrupt::Stat   => 1 << 1,
+            Interrupt::Timer  => 1 << 2,
+            Interrupt::Serial => 1 << 3,
+            Interrupt::Joypad => 1 << 4,
+        }
+    }
+}
+
 impl Cpu {
     pub fn new(cartridge: Cartridge, model: GBModel) -> Self {
         assert!(!(matches!(model, GBModel::CGB) && !cartridge.cgb_compatible()), 
@@ -57,19 +96,112 @@ impl Cpu {
             let mut bus = Bus::new(cartridge, model);
             bus.write_byte(0xFF40, 0x91);
             bus.write_byte(0xFF41, 0x81);
+            Cpu::from_bus(bus, model)
+        }
+    }
+
+    pub fn get_audio_output(&mut self) -> Option<[[f32; 2]; AUDIO_SAMPLES]> {
+        self.bus.get_audio_output()
+    }
+
+    pub fn get_display_output(&mut self) -> Option<&[u8; LCD_BYTE_WIDTH * LCD_HEIGHT]> {
+        self.bus.get_display_output()
+    }
+
+    #[cfg(test)]
+    pub fn get_index_output(&self) -> &[u16; LCD_WIDTH * LCD_HEIGHT] {
+        self.bus.get_index_output()
+    }
+
+    pub fn entered_hblank(&self) -> bool {
+        self.bus.entered_hblank()
+    }
+
+    pub fn update_joypad(&mut self, status: u8) {
+        self.bus.update_joypad(status)
+    }
 
-            match model {
-                GBModel::DMG => {
-                    Cpu::make_cpu(0x01B0, 0x0013, 0x00D8, 0x014D, 0x0100, 0xFFFE, model, bus)
-                }
-                GBModel::CGB => {
-                    Cpu::make_cpu(0x1180, 0x0000, 0xFF56, 0x000D, 0x0100, 0xFFFE, model, bus)
-                }
+    #[allow(dead_code)]
+    pub fn get_serial_output(&self) -> &str {
+        self.bus.get_serial_output()
+    }
+
+    pub fn save_mbc_state(&mut self) {
+        self.bus.save_mbc_state()
+    }
+
+    /// Checks every memory access by the CPU against the given watchpoints (None to stop checking).
+    pub fn set_watchpoints(&mut self, watchpoints: Option<Watchpoints>) {
+        self.bus.set_watchpoints(watchpoints)
+    }
+
+    /// Returns (and clears) the first watchpoint hit since the last call.
+    pub fn take_watch_hit(&self) -> Option<WatchHit> {
+        self.bus.take_watch_hit()
+    }
+
+    /// Enables (or disables) reporting of exceptions, e.g. reads of uninitialized RAM (see Exceptions).
+    pub fn set_exceptions(&mut self, enabled: bool) {
+        self.bus.set_exceptions(enabled)
+    }
+
+    /// Returns (and clears) the exceptions raised by the last step, where pc
+    /// is the address of the instruction that was stepped.
+    pub fn take_exceptions(&mut self, pc: u16) -> Vec<Exception> {
+        self.bus.take_exceptions(pc, self.sp.full())
+    }
+
+    /// Logs every ROM byte read by the CPU (or copied by DMA) into the given code/data log
+    /// (None to stop logging).
+    pub fn set_code_data_log(&mut self, log: Option<CodeDataLog>) {
+        self.bus.set_code_data_log(log)
+    }
+
+    pub fn code_data_log(&self) -> Option<&CodeDataLog> {
+        self.bus.code_data_log()
+    }
+
+    /// Records interrupts, DMA, LCD register writes and bank switches into the given event log
+    /// (None to stop recording).
+    pub fn set_event_log(&mut self, log: Option<EventLog>) {
+        self.bus.set_event_log(log)
+    }
+
+    pub fn event_log(&self) -> Option<&EventLog> {
+        self.bus.event_log()
+    }
+
+    /// Makes LY always read as 0x90, which gameboy-doctor logs expect.
+    pub fn set_ly_stub(&mut self, enabled: bool) {
+        self.bus.set_ly_stub(enabled)
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn load_save(&mut self, data: Vec<u8>, save_type: &str) {
+        self.bus.load_save(data, save_type);
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn save_id(&self) -> Option<String> {
+        self.bus.save_id()
+    }
+}
+
+impl<B: MemoryBus> Cpu<B> {
+    /// Makes a CPU connected to the given bus, with registers set to 
+    /// their values after the given model's boot ROM (PC = 0x0100).
+    pub fn from_bus(bus: B, model: GBModel) -> Self {
+        match model {
+            GBModel::DMG => {
+                Cpu::make_cpu(0x01B0, 0x0013, 0x00D8, 0x014D, 0x0100, 0xFFFE, model, bus)
+            }
+            GBModel::CGB => {
+                Cpu::make_cpu(0x1180, 0x0000, 0xFF56, 0x000D, 0x0100, 0xFFFE, model, bus)
             }
         }
     }
 
-    fn make_cpu(af: u16, bc: u16, de: u16, hl: u16, pc: u16, sp: u16, model: GBModel, bus: Bus) -> Self {
+    fn make_cpu(af: u16, bc: u16, de: u16, hl: u16, pc: u16, sp: u16, model: GBModel, bus: B) -> Self {
         Cpu { 
             bus,
             model,
@@ -77,7 +209,6 @@ impl Cpu {
             ime: false,
             halted: false,
             halt_bug: false,
-            halt_triggered: false,
             t_cycles_so_far: 0,
             af: Register(af),
             bc: Register(bc),
@@ -85,13 +216,18 @@ impl Cpu {
             hl: Register(hl),
             pc: Register(pc),
             sp: Register(sp),
+            tracer: None,
+            debug_hooks: false,
+            debug_break: false,
             do_speed_switch: false,
         }
     }
 
     /// Steps through all parts of the emulator over the period
     /// that the next CPU instruction will take; returns that period's length in T-cycles.
+    /// NOTE: components are stepped alongside each M-cycle of the instruction (see partial_step in bus)
     pub fn step(&mut self) -> u32 {
+        self.bus.begin_step();
         let t_cycles = self.cycle();
 
         if matches!(self.model, GBModel::CGB) && self.do_speed_switch {
@@ -100,19 +236,22 @@ impl Cpu {
             return 2560 
         }
 
-        self.bus.step(t_cycles);
-       
-        t_cycles
+        // CPU is stalled for the duration of any VRAM DMA transfer
+        t_cycles + self.bus.end_step(self.halted)
     }
 
-    /// Do a CPU fetch-execute cycle and return the number of T-cycles taken.
+    /// Do a CPU fetch-execute cycle (and dispatch any pending interrupt)
+    /// and return the number of T-cycles taken.
     fn cycle(&mut self) -> u32 {
-        self.halt_triggered = false;
-
-        if self.scheduled_ei {
-            self.ime = true;
-            self.scheduled_ei = false;
+        if !self.halted {
+            if let Some(mut tracer) = self.tracer.take() {
+                tracer.trace(self.doctor_line(), self.pc.full(), self.bus.rom_bank());
+                self.tracer = Some(tracer);
+            }
         }
+
+        // EI only takes effect after the instruction following it
+        let enable_ime = self.scheduled_ei;
         
         let mut t_cycles = if !self.halted {
             self.execute_next_instruction() * 4
@@ -120,22 +259,21 @@ impl Cpu {
             4
         };
 
-        match self.get_pending_interrupt() {
-            Some(interrupt) => {
-                if self.ime {
-                    t_cycles += self.handle_interrupt(interrupt) * 4;
-                } else if self.halt_triggered {
-                    self.halt_bug = true;
-                }
-
-                self.ime = false;
-                self.halted = false;
-            },
-            None => {}
+        if enable_ime && self.scheduled_ei {
+            self.ime = true;
+            self.scheduled_ei = false;
+        }
+
+        if self.get_pending_interrupt().is_some() {
+            self.halted = false;
+
+            if self.ime {
+                t_cycles += self.handle_interrupt() * 4;
+            }
         }
 
         if t_cycles > self.t_cycles_so_far {
-            self.bus.partial_step(t_cycles - self.t_cycles_so_far);
+            self.bus.tick(t_cycles - self.t_cycles_so_far);
         }
         self.t_cycles_so_far = 0;
 
@@ -144,11 +282,10 @@ impl Cpu {
     
     /// Returns the next pending interrupt by priority
     fn get_pending_interrupt(&mut self) -> Option<Interrupt>{
-        let interrupt_enable: u8 =  self.bus.read_byte(0xFFFF);
-        let interrupt_flag: u8 = self.bus.read_byte(0xFF0F);
+        let pending = self.bus.pending_interrupts();
 
         for bit in 0..=4 {   
-            if interrupt_enable & (1 << bit) != 0 && interrupt_flag & (1 << bit) != 0 {
+            if pending & (1 << bit) != 0 {
                 let interrupt: Interrupt = match bit {
                     0 => VBlank,
                     1 => Stat,
@@ -165,54 +302,99 @@ impl Cpu {
         None
     }
 
-    pub fn get_audio_output(&mut self) -> Option<[[f32; 2]; AUDIO_SAMPLES]> {
-        self.bus.get_audio_output()
+    #[allow(dead_code)]
+    pub fn read_byte(&self, addr: u16) -> u8 {
+        self.bus.read_byte(addr)
     }
 
-    pub fn get_display_output(&mut self) -> Option<&[u8; LCD_BYTE_WIDTH * LCD_HEIGHT]> {
-        self.bus.get_display_output()
+    /// Returns the current state in the gameboy-doctor log format.
+    fn doctor_line(&self) -> String {
+        let pc = self.pc.full();
+        let pcmem: Vec<String> = (0..4)
+            .map(|i| format!("{:02X}", self.bus.peek_byte(pc.wrapping_add(i))))
+            .collect();
+
+        format!("A:{:02X} F:{:02X} B:{:02X} C:{:02X} D:{:02X} E:{:02X} H:{:02X} L:{:02X} SP:{:04X} PC:{:04X} PCMEM:{}",
+            self.af.hi(), self.af.lo(), self.bc.hi(), self.bc.lo(), self.de.hi(), self.de.lo(),
+            self.hl.hi(), self.hl.lo(), self.sp.full(), pc, pcmem.join(","))
     }
 
-    pub fn entered_hblank(&self) -> bool {
-        self.bus.entered_hblank()
+    /// Traces every instruction from now on (see Tracer).
+    pub fn set_tracer(&mut self, tracer: Tracer) {
+        self.tracer = Some(tracer);
     }
 
-    pub fn update_joypad(&mut self, status: u8) {
-        self.bus.update_joypad(status)
+    /// Returns true if a tracer is attached and has stopped.
+    pub fn trace_stopped(&self) -> bool {
+        self.tracer.as_ref().is_some_and(|tracer| tracer.stopped())
     }
 
-    #[allow(dead_code)]
-    pub fn get_serial_output(&self) -> &str {
-        self.bus.get_serial_output()
+    /// Returns the ROM bank currently mapped to 0x4000-0x7FFF.
+    pub fn rom_bank(&self) -> usize {
+        self.bus.rom_bank()
     }
 
-    pub fn save_mbc_state(&mut self) {
-        self.bus.save_mbc_state()
+    /// Reads a byte without it counting as a memory access (see MemoryBus::peek_byte).
+    pub fn peek_byte(&self, addr: u16) -> u8 {
+        self.bus.peek_byte(addr)
     }
 
-    #[allow(dead_code)]
-    pub fn read_byte(&self, addr: u16) -> u8 {
-        self.bus.read_byte(addr)
+    pub fn bus(&self) -> &B {
+        &self.bus
     }
 
-    #[cfg(target_arch = "wasm32")]
-    pub fn load_save(&mut self, data: Vec<u8>, save_type: &str) {
-        self.bus.load_save(data, save_type);
+    pub fn bus_mut(&mut self) -> &mut B {
+        &mut self.bus
     }
 
-    #[cfg(target_arch = "wasm32")]
-    pub fn save_id(&self) -> Option<String> {
-        self.bus.save_id()
+    pub fn registers(&self) -> Registers {
+        Registers {
+            af: self.af.full(),
+            bc: self.bc.full(),
+            de: self.de.full(),
+            hl: self.hl.full(),
+            sp: self.sp.full(),
+            pc: self.pc.full(),
+            ime: self.ime,
+            halted: self.halted,
+        }
+    }
+
+    /// NOTE: the lower 4 bits of F are always 0.
+    pub fn set_registers(&mut self, registers: Registers) {
+        self.af.set(registers.af & 0xFFF0);
+        self.bc.set(registers.bc);
+        self.de.set(registers.de);
+        self.hl.set(registers.hl);
+        self.sp.set(registers.sp);
+        self.pc.set(registers.pc);
+        self.ime = registers.ime;
+        self.halted = registers.halted;
     }
 }
 
+/// Snapshot of the CPU's registers and state.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Registers {
+    pub af: u16,
+    pub bc: u16,
+    pub de: u16,
+    pub hl: u16,
+    pub sp: u16,
+    pub pc: u16,
+    pub ime: bool,
+    pub halted: bool,
+}
+
 #[cfg(test)]
 mod tests {
-    use super::test_helpers::test_blargg_rom;
+    use super::test_helpers::{test_blargg_rom, test_mooneye_rom};
+    use super::GBModel::DMG;
 
     const CPU_INSTR: &str = "roms/tests/cpu_instrs.gb";
     const MEM_TIMING: &str = "roms/tests/mem_timing.gb";
     const INSTR_TIMING: &str = "roms/tests/instr_timing.gb";
+    const OAM_BUG: &str = "roms/tests/oam_bug.gb";
 
     #[test]
     fn cpu_instr_test() {
@@ -228,6 +410,55 @@ mod tests {
     fn cpu_instr_timing_test() {
         test_blargg_rom(INSTR_TIMING, super::GBModel::DMG);
     }
+
+    #[test]
+    fn cpu_oam_bug_test() {
+        test_blargg_rom(OAM_BUG, super::GBModel::DMG);
+    }
+
+    #[test]
+    fn cpu_interrupts_test() {
+        test_mooneye_rom("roms/tests/mooneye/acceptance/interrupts/ie_push.gb", DMG);
+        test_mooneye_rom("roms/tests/mooneye/acceptance/if_ie_registers.gb", DMG);
+        test_mooneye_rom("roms/tests/mooneye/acceptance/intr_timing.gb", DMG);
+        test_mooneye_rom("roms/tests/mooneye/acceptance/rapid_di_ei.gb", DMG);
+        test_mooneye_rom("roms/tests/mooneye/acceptance/reti_intr_timing.gb", DMG);
+    }
+
+    #[test]
+    fn cpu_ei_di_test() {
+        test_mooneye_rom("roms/tests/mooneye/acceptance/ei_sequence.gb", DMG);
+        test_mooneye_rom("roms/tests/mooneye/acceptance/ei_timing.gb", DMG);
+        test_mooneye_rom("roms/tests/mooneye/acceptance/di_timing-GS.gb", DMG);
+    }
+
+    #[test]
+    fn cpu_halt_test() {
+        test_mooneye_rom("roms/tests/mooneye/acceptance/halt_ime0_ei.gb", DMG);
+        test_mooneye_rom("roms/tests/mooneye/acceptance/halt_ime0_nointr_timing.gb", DMG);
+        test_mooneye_rom("roms/tests/mooneye/acceptance/halt_ime1_timing.gb", DMG);
+        test_mooneye_rom("roms/tests/mooneye/acceptance/halt_ime1_timing2-GS.gb", DMG);
+    }
+
+    #[test]
+    fn cpu_mooneye_timing_test() {
+        test_mooneye_rom("roms/tests/mooneye/acceptance/call_timing.gb", DMG);
+        test_mooneye_rom("roms/tests/mooneye/acceptance/call_timing2.gb", DMG);
+        test_mooneye_rom("roms/tests/mooneye/acceptance/call_cc_timing.gb", DMG);
+        test_mooneye_rom("roms/tests/mooneye/acceptance/call_cc_timing2.gb", DMG);
+        test_mooneye_rom("roms/tests/mooneye/acceptance/push_timing.gb", DMG);
+        test_mooneye_rom("roms/tests/mooneye/acceptance/pop_timing.gb", DMG);
+        test_mooneye_rom("roms/tests/mooneye/acceptance/rst_timing.gb", DMG);
+        test_mooneye_rom("roms/tests/mooneye/acceptance/ret_timing.gb", DMG);
+        test_mooneye_rom("roms/tests/mooneye/acceptance/ret_cc_timing.gb", DMG);
+        test_mooneye_rom("roms/tests/mooneye/acceptance/reti_timing.gb", DMG);
+        test_mooneye_rom("roms/tests/mooneye/acceptance/jp_timing.gb", DMG);
+        test_mooneye_rom("roms/tests/mooneye/acceptance/jp_cc_timing.gb", DMG);
+        test_mooneye_rom("roms/tests/mooneye/acceptance/ld_hl_sp_e_timing.gb", DMG);
+        test_mooneye_rom("roms/tests/mooneye/acceptance/add_sp_e_timing.gb", DMG);
+        test_mooneye_rom("roms/tests/mooneye/acceptance/div_timing.gb", DMG);
+        test_mooneye_rom("roms/tests/mooneye/acceptance/oam_dma_timing.gb", DMG);
+    }
 }
 
 #[cfg(test)]
@@ -255,6 +486,43 @@ pub mod test_helpers {
         cpu.hl.lo() == 0x42
     }
     
+    pub enum MooneyeResult {
+        Pass,
+        Fail,
+        Timeout,
+    }
+
+    /// Runs a Mooneye test ROM until it passes or fails (by its register signature when
+    /// the LD B,B breakpoint executes), or until the given number of T-cycles have elapsed.
+    pub fn run_mooneye_rom(cpu: &mut Cpu, timeout: u64) -> MooneyeResult {
+        let mut cycles: u64 = 0;
+        while cycles < timeout {
+            let breakpoint = cpu.peek_byte(cpu.pc.full()) == 0x40;
+            cycles += cpu.step() as u64;
+
+            if breakpoint && mooneye_fail_check(cpu) {
+                return MooneyeResult::Fail;
+            } else if breakpoint && mooneye_pass_check(cpu) {
+                return MooneyeResult::Pass;
+            }
+        } 
+
+        MooneyeResult::Timeout
+    }
+
+    /// Runs until the next instruction is LD B,B (used as a breakpoint by test ROMs),
+    /// or until the given number of T-cycles have elapsed; returns true if the breakpoint was reached.
+    pub fn run_until_breakpoint(cpu: &mut Cpu, timeout: u64) -> bool {
+        let mut cycles: u64 = 0;
+        while cycles < timeout {
+            if cpu.read_byte(cpu.pc.full()) == 0x40 {
+                return true;
+            }
+            cycles += cpu.step() as u64;
+        }
+        false
+    }
+
     pub fn test_mooneye_rom(test_rom_path: &str, model: GBModel) {
         let cartridge = Cartridge::from_file(test_rom_path, false);
         let mut cpu = Cpu::new(cartridge, model);
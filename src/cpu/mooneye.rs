@@ -0,0 +1,157 @@
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+
+use crate::Cartridge;
+use super::test_helpers::{run_mooneye_rom, MooneyeResult};
+use super::{Cpu, GBModel};
+
+const MOONEYE_DIR: &str = "roms/tests/mooneye";
+
+// Mooneye tests finish within a few frames, so this is much shorter than TEST_TIMEOUT
+const SUITE_TIMEOUT: u64 = 1 << 26;
+
+enum Outcome {
+    Pass,
+    Fail,
+    Timeout,
+    Panic,
+    Skip(&'static str),
+}
+
+impl Outcome {
+    fn display(&self) -> String {
+        match self {
+            Outcome::Pass => String::from("PASS"),
+            Outcome::Fail => String::from("FAIL"),
+            Outcome::Timeout => String::from("TIMEOUT"),
+            Outcome::Panic => String::from("PANIC"),
+            Outcome::Skip(reason) => format!("SKIP ({})", reason),
+        }
+    }
+}
+
+/// Models a test ROM should be run on, based on the suffix of its file name.
+enum Models {
+    Run(Vec<GBModel>),
+    Unsupported,
+}
+
+/// Decodes the model suffix of a test ROM's name (e.g. boot_regs-dmgABC, di_timing-GS),
+/// where G = DMG, S = SGB, C = CGB, A = AGB (or dmg*, mgb, sgb*, cgb*, agb, ags in lowercase).
+/// ROMs without a suffix run on every model.
+fn models_for(name: &str) -> Models {
+    let suffix = match name.rsplit_once('-') {
+        Some((_, suffix)) => suffix,
+        None => return Models::Run(vec![GBModel::DMG, GBModel::CGB]),
+    };
+
+    // specific hardware revisions, which are not emulated
+    if suffix == "dmg0" || suffix == "cgb0" || suffix == "mgb" || suffix.starts_with("sgb")
+        || suffix == "agb" || suffix == "ags" {
+        return Models::Unsupported;
+    }
+
+    if suffix.starts_with("dmg") {
+        return Models::Run(vec![GBModel::DMG]);
+    }
+    if suffix.starts_with("cgb") {
+        return Models::Run(vec![GBModel::CGB]);
+    }
+
+    if !suffix.chars().all(|c| "GSCAE".contains(c)) {
+        return Models::Run(vec![GBModel::DMG, GBModel::CGB]);
+    }
+
+    let mut models = Vec::new();
+    if suffix.contains('G') {
+        models.push(GBModel::DMG);
+    }
+    if suffix.contains('C') {
+        models.push(GBModel::CGB);
+    }
+
+    if models.is_empty() { Models::Unsupported } else { Models::Run(models) }
+}
+
+/// Recursively collects every ROM under dir.
+fn find_roms(dir: &Path, roms: &mut Vec<PathBuf>) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            find_roms(&path, roms);
+        } else if matches!(path.extension().and_then(|ext| ext.to_str()), Some("gb" | "gbc")) {
+            roms.push(path);
+        }
+    }
+}
+
+fn run_rom(path: &str, model: GBModel) -> Outcome {
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        let cartridge = Cartridge::from_file(path, false);
+        if matches!(model, GBModel::CGB) && !cartridge.cgb_compatible() {
+            return Outcome::Skip("not a CGB cartridge");
+        }
+
+        let mut cpu = Cpu::new(cartridge, model);
+        match run_mooneye_rom(&mut cpu, SUITE_TIMEOUT) {
+            MooneyeResult::Pass => Outcome::Pass,
+            MooneyeResult::Fail => Outcome::Fail,
+            MooneyeResult::Timeout => Outcome::Timeout,
+        }
+    }));
+
+    result.unwrap_or(Outcome::Panic)
+}
+
+/// Runs every Mooneye test ROM and prints a table of results (run with --nocapture to see it).
+/// Needs the built ROMs of the Mooneye Test Suite, extracted into MOONEYE_DIR (skipped otherwise).
+#[test]
+fn mooneye_suite() {
+    let mut roms = Vec::new();
+    find_roms(Path::new(MOONEYE_DIR), &mut roms);
+    if roms.is_empty() {
+        println!("Skipping the Mooneye Test Suite: no ROMs found in {}", MOONEYE_DIR);
+        return;
+    }
+    roms.sort();
+
+    // silence panic messages from failing ROMs, so the table stays readable
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+
+    let mut rows = Vec::new();
+    for rom in &roms {
+        let path = rom.to_string_lossy();
+        let name = rom.file_stem().unwrap().to_string_lossy();
+        let test_name = path.trim_start_matches(MOONEYE_DIR).trim_start_matches('/');
+
+        match models_for(&name) {
+            Models::Run(models) => {
+                for model in models {
+                    rows.push((test_name.to_string(), format!("{:?}", model), run_rom(&path, model)));
+                }
+            }
+            Models::Unsupported => {
+                rows.push((test_name.to_string(), String::from("-"), Outcome::Skip("unsupported model")));
+            }
+        }
+    }
+
+    panic::set_hook(hook);
+
+    let width = rows.iter().map(|(name, _, _)| name.len()).max().unwrap_or(0);
+    let passed = rows.iter().filter(|(_, _, outcome)| matches!(outcome, Outcome::Pass)).count();
+    let skipped = rows.iter().filter(|(_, _, outcome)| matches!(outcome, Outcome::Skip(_))).count();
+
+    println!();
+    for (name, model, outcome) in &rows {
+        println!("{:<width$}  {:<3}  {}", name, model, outcome.display(), width = width);
+    }
+    println!("\n{}/{} passed ({} skipped)", passed, rows.len() - skipped, skipped);
+}
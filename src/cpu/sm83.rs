@@ -0,0 +1,165 @@
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+
+use serde_json::Value;
+
+use super::{BusAccess, Cpu, FlatBus, GBModel, MemoryBus, RecordingBus};
+
+const SM83_DIR: &str = "roms/tests/sm83/v1";
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Activity {
+    Read(u16, u8),
+    Write(u16, u8),
+    Internal,
+}
+
+type TestBus = RecordingBus<FlatBus>;
+
+fn reg(state: &Value, name: &str) -> u16 {
+    state[name].as_u64().unwrap_or_else(|| panic!("missing register {}", name)) as u16
+}
+
+fn make_cpu(initial: &Value) -> Cpu<TestBus> {
+    let mut ram = FlatBus::new();
+    for entry in initial["ram"].as_array().unwrap() {
+        ram.write_byte(entry[0].as_u64().unwrap() as u16, entry[1].as_u64().unwrap() as u8);
+    }
+    if let Some(ie) = initial["ie"].as_u64() {
+        ram.write_byte(0xFFFF, ie as u8);
+    }
+    let bus = RecordingBus::new(ram);
+
+    let af = (reg(initial, "a") << 8) | reg(initial, "f");
+    let bc = (reg(initial, "b") << 8) | reg(initial, "c");
+    let de = (reg(initial, "d") << 8) | reg(initial, "e");
+    let hl = (reg(initial, "h") << 8) | reg(initial, "l");
+    let mut cpu = Cpu::make_cpu(af, bc, de, hl, reg(initial, "pc"), reg(initial, "sp"), GBModel::DMG, bus);
+    cpu.ime = initial["ime"].as_u64() == Some(1);
+    cpu
+}
+
+/// Returns the first difference between the CPU's state and the expected final state.
+fn check_final(cpu: &Cpu<TestBus>, expected: &Value) -> Result<(), String> {
+    let registers = [
+        ("a", cpu.af.hi() as u16), ("f", cpu.af.lo() as u16),
+        ("b", cpu.bc.hi() as u16), ("c", cpu.bc.lo() as u16),
+        ("d", cpu.de.hi() as u16), ("e", cpu.de.lo() as u16),
+        ("h", cpu.hl.hi() as u16), ("l", cpu.hl.lo() as u16),
+        ("pc", cpu.pc.full()), ("sp", cpu.sp.full()),
+    ];
+    for (name, actual) in registers {
+        if reg(expected, name) != actual {
+            return Err(format!("{} = {:#06X}, expected {:#06X}", name, actual, reg(expected, name)));
+        }
+    }
+
+    if let Some(ime) = expected["ime"].as_u64() {
+        if cpu.ime != (ime == 1) {
+            return Err(format!("ime = {}, expected {}", cpu.ime as u8, ime));
+        }
+    }
+
+    for entry in expected["ram"].as_array().unwrap() {
+        let addr = entry[0].as_u64().unwrap() as u16;
+        let byte = entry[1].as_u64().unwrap() as u8;
+        let actual = cpu.bus.inner().read_byte(addr);
+        if actual != byte {
+            return Err(format!("[{:#06X}] = {:#04X}, expected {:#04X}", addr, actual, byte));
+        }
+    }
+
+    Ok(())
+}
+
+/// Decodes a cycle as either null, or [addr, value, pins] where pins is e.g. "r-m" or "-wm".
+fn parse_cycle(cycle: &Value) -> Activity {
+    let cycle = match cycle.as_array() {
+        Some(cycle) => cycle,
+        None => return Activity::Internal,
+    };
+
+    let addr = cycle[0].as_u64().unwrap_or(0) as u16;
+    let byte = cycle[1].as_u64().unwrap_or(0) as u8;
+    match cycle[2].as_str().unwrap_or("") {
+        pins if pins.contains('r') => Activity::Read(addr, byte),
+        pins if pins.contains('w') => Activity::Write(addr, byte),
+        _ => Activity::Internal,
+    }
+}
+
+/// Returns the memory access (if any) made in each M-cycle.
+fn bus_activity(bus: &mut TestBus) -> Vec<Activity> {
+    let accesses = bus.take_accesses();
+    (0..bus.cycles() / 4).map(|m_cycle| {
+        accesses.iter().find_map(|access| match *access {
+            BusAccess::Read { cycle, addr, byte } if cycle == m_cycle * 4 => Some(Activity::Read(addr, byte)),
+            BusAccess::Write { cycle, addr, byte } if cycle == m_cycle * 4 => Some(Activity::Write(addr, byte)),
+            _ => None,
+        }).unwrap_or(Activity::Internal)
+    }).collect()
+}
+
+fn check_cycles(cpu: &mut Cpu<TestBus>, expected: &Value) -> Result<(), String> {
+    let expected: Vec<Activity> = expected.as_array().unwrap().iter().map(parse_cycle).collect();
+    let activity = bus_activity(&mut cpu.bus);
+    if activity != expected {
+        return Err(format!("bus activity {:X?}, expected {:X?}", activity, expected));
+    }
+    Ok(())
+}
+
+fn run_test(test: &Value) -> Result<(), String> {
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        let mut cpu = make_cpu(&test["initial"]);
+        cpu.step();
+        check_final(&cpu, &test["final"])?;
+        check_cycles(&mut cpu, &test["cycles"])
+    }));
+
+    result.unwrap_or_else(|_| Err(String::from("panicked")))
+}
+
+/// Runs every test vector of the SM83 single-step tests (one JSON file per opcode),
+/// and reports every opcode with a test whose final state or bus activity differs.
+/// Needs the v1 JSON files of the SingleStepTests sm83 repository, copied into SM83_DIR (skipped otherwise).
+#[test]
+fn sm83_single_step_tests() {
+    let mut files: Vec<PathBuf> = match fs::read_dir(SM83_DIR) {
+        Ok(entries) => entries.flatten().map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    if files.is_empty() {
+        println!("Skipping the SM83 single-step tests: no tests found in {}", SM83_DIR);
+        return;
+    }
+    files.sort();
+
+    // silence panic messages (e.g. from STOP), so the report stays readable
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+
+    let mut failed_opcodes = Vec::new();
+    for file in &files {
+        let opcode = file.file_stem().unwrap().to_string_lossy().to_string();
+        let tests: Value = serde_json::from_str(&fs::read_to_string(file).unwrap()).unwrap();
+        let tests = tests.as_array().unwrap();
+
+        let failures: Vec<(String, String)> = tests.iter()
+            .filter_map(|test| run_test(test).err().map(|err| (test["name"].to_string(), err)))
+            .collect();
+
+        if let Some((name, err)) = failures.first() {
+            println!("{}: {}/{} failed, first: {}: {}", opcode, failures.len(), tests.len(), name, err);
+            failed_opcodes.push(opcode);
+        }
+    }
+
+    panic::set_hook(hook);
+
+    assert!(failed_opcodes.is_empty(), "{}/{} opcodes failed: {}",
+        failed_opcodes.len(), files.len(), failed_opcodes.join(", "));
+}
@@ -0,0 +1,143 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Lines, Write};
+use std::rc::Rc;
+
+use crate::symbols::Symbols;
+
+// number of matching lines shown before the first difference in compare mode
+const CONTEXT_LINES: usize = 8;
+
+enum TraceMode {
+    Log(BufWriter<File>),
+    Compare(Lines<BufReader<File>>),
+}
+
+/// Per-instruction trace in the gameboy-doctor format, which is either written
+/// to a file, or compared against a reference log (stopping at the first difference).
+/// Annotated logs also have comment lines (; Label) before labelled instructions, and for debug
+/// messages, which are skipped when comparing; plain logs stay byte-compatible with gameboy-doctor.
+pub struct Tracer {
+    mode: TraceMode,
+    annotated: bool,
+    symbols: Option<Rc<Symbols>>,
+    line_num: usize,
+    history: VecDeque<String>,
+    stopped: bool,
+}
+
+impl Tracer {
+    pub fn log_to(path: &str) -> io::Result<Self> {
+        let file = BufWriter::new(File::create(path)?);
+        Ok(Tracer::new(TraceMode::Log(file)))
+    }
+
+    pub fn compare_with(path: &str) -> io::Result<Self> {
+        let lines = BufReader::new(File::open(path)?).lines();
+        Ok(Tracer::new(TraceMode::Compare(lines)))
+    }
+
+    fn new(mode: TraceMode) -> Self {
+        Tracer {
+            mode,
+            annotated: false,
+            symbols: None,
+            line_num: 0,
+            history: VecDeque::with_capacity(CONTEXT_LINES),
+            stopped: false,
+        }
+    }
+
+    /// Enables comment lines in the log (which gameboy-doctor does not expect).
+    pub fn set_annotated(&mut self, annotated: bool) {
+        self.annotated = annotated;
+    }
+
+    /// Writes a comment line with the label before each labelled instruction (when logging annotated).
+    pub fn set_symbols(&mut self, symbols: Rc<Symbols>) {
+        self.symbols = Some(symbols);
+    }
+
+    /// Writes a comment line (e.g. a debug message) to an annotated log; ignored otherwise.
+    pub fn comment(&mut self, text: &str) {
+        if let TraceMode::Log(file) = &mut self.mode {
+            if self.annotated && !self.stopped && writeln!(file, "; {}", text).is_err() {
+                self.stopped = true;
+            }
+        }
+    }
+
+    /// Returns true once the trace has stopped (the first difference,
+    /// or the end of the reference log was reached, or writing failed).
+    pub fn stopped(&self) -> bool {
+        self.stopped
+    }
+
+    /// Handles the trace line of the next instruction, which is at pc
+    /// (with bank mapped to 0x4000-0x7FFF).
+    pub fn trace(&mut self, line: String, pc: u16, bank: usize) {
+        if self.stopped {
+            return;
+        }
+        self.line_num += 1;
+
+        match &mut self.mode {
+            TraceMode::Log(file) => {
+                let label = self.symbols.as_ref()
+                    .filter(|_| self.annotated)
+                    .and_then(|symbols| symbols.label(pc, bank));
+                let result = match label {
+                    Some(label) => writeln!(file, "; {}\n{}", label, line),
+                    None => writeln!(file, "{}", line),
+                };
+                if let Err(e) = result {
+                    println!("Stopped trace: {}", e);
+                    self.stopped = true;
+                }
+            }
+            TraceMode::Compare(reference) => {
+                let mut next = reference.next();
+                while matches!(&next, Some(Ok(expected)) if expected.starts_with(';')) {
+                    next = reference.next();
+                }
+
+                match next {
+                    Some(Ok(expected)) if expected.trim_end() == line => {}
+                    Some(Ok(expected)) => {
+                        self.print_difference(expected.trim_end(), &line);
+                        self.stopped = true;
+                    }
+                    Some(Err(e)) => {
+                        println!("Stopped trace: error reading reference log: {}", e);
+                        self.stopped = true;
+                    }
+                    None => {
+                        println!("Trace matched all {} lines of the reference log", self.line_num - 1);
+                        self.stopped = true;
+                    }
+                }
+            }
+        }
+
+        if self.history.len() == CONTEXT_LINES {
+            self.history.pop_front();
+        }
+        self.history.push_back(line);
+    }
+
+    fn print_difference(&self, expected: &str, actual: &str) {
+        println!("Trace differs from the reference log at line {}:", self.line_num);
+        for (i, line) in self.history.iter().enumerate() {
+            println!("  {:>8}  {}", self.line_num - self.history.len() + i, line);
+        }
+        println!("- {:>8}  {}", self.line_num, expected);
+        println!("+ {:>8}  {}", self.line_num, actual);
+
+        let fields: Vec<String> = expected.split_whitespace()
+            .zip(actual.split_whitespace())
+            .filter(|(e, a)| e != a)
+            .map(|(e, a)| format!("{} (got {})", e, a))
+            .collect();
+        println!("differing fields: {}", fields.join(", "));
+    }
+}
@@ -0,0 +1,91 @@
+use std::cell::Cell;
+
+/// Kind of memory access a watchpoint triggers on.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+/// A watched access made by the CPU.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct WatchHit {
+    pub id: usize,
+    pub access: Access,
+    pub addr: u16,
+    pub byte: u8,
+}
+
+struct Watchpoint {
+    id: usize,
+    start: u16,
+    end: u16,
+    read: bool,
+    write: bool,
+}
+
+/// Read/write watchpoints on address ranges, checked by the bus on every CPU access;
+/// only the first hit is kept until it is taken.
+#[derive(Default)]
+pub struct Watchpoints {
+    list: Vec<Watchpoint>,
+    hit: Cell<Option<WatchHit>>,
+}
+
+impl Watchpoints {
+    pub fn new() -> Self {
+        Watchpoints::default()
+    }
+
+    /// Watches start..=end for reads and/or writes; id is reported back in hits.
+    pub fn add(&mut self, id: usize, start: u16, end: u16, read: bool, write: bool) {
+        self.list.push(Watchpoint { id, start, end, read, write });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    pub fn check(&self, access: Access, addr: u16, byte: u8) {
+        if self.hit.get().is_some() {
+            return;
+        }
+
+        let hit = self.list.iter().find(|watchpoint| {
+            let watched = match access {
+                Access::Read => watchpoint.read,
+                Access::Write => watchpoint.write,
+            };
+            watched && (watchpoint.start..=watchpoint.end).contains(&addr)
+        });
+
+        if let Some(watchpoint) = hit {
+            self.hit.set(Some(WatchHit { id: watchpoint.id, access, addr, byte }));
+        }
+    }
+
+    /// Returns (and clears) the first hit since the last call.
+    pub fn take_hit(&self) -> Option<WatchHit> {
+        self.hit.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Access, WatchHit, Watchpoints};
+
+    #[test]
+    fn watchpoint_test() {
+        let mut watchpoints = Watchpoints::new();
+        watchpoints.add(1, 0xC000, 0xC0FF, false, true);
+        watchpoints.add(2, 0xFF40, 0xFF40, true, false);
+
+        watchpoints.check(Access::Read, 0xC010, 0x12);
+        assert_eq!(watchpoints.take_hit(), None);
+
+        watchpoints.check(Access::Write, 0xC0FF, 0x34);
+        watchpoints.check(Access::Read, 0xFF40, 0x91);
+        assert_eq!(watchpoints.take_hit(), Some(WatchHit { id: 1, access: Access::Write, addr: 0xC0FF, byte: 0x34 }));
+        assert_eq!(watchpoints.take_hit(), None);
+    }
+}
@@ -0,0 +1,603 @@
+use std::collections::VecDeque;
+use std::io::{self, BufRead, Write};
+use std::rc::Rc;
+
+use crate::cli::number;
+use crate::cpu::{Access, Cpu, Registers, WatchHit, Watchpoints};
+use crate::disasm::{disassemble, format_addr};
+use crate::ppu::Layer;
+use crate::symbols::Symbols;
+
+// number of executed instructions shown before PC when paused
+const HISTORY_LINES: usize = 4;
+// number of instructions shown from PC when paused
+const UPCOMING_LINES: usize = 6;
+
+const HELP: &str = "\
+commands (an empty line repeats the last command):
+    c, continue                 run until a breakpoint or watchpoint is hit
+    s, step [n]                 execute n instructions (default 1)
+    n, next                     step over CALL and RST
+    finish                      run until the current function returns
+    b, break <addr> [if <cond>] break at addr, e.g. 'break 03:4000 if a == $12' or 'break PlayerUpdate'
+    b, break if <cond>          break on any instruction where cond holds
+    watch <addr>[-<end>] [rwx]  break on reads/writes/execution of an address range (default w)
+    d, delete <id>              delete a breakpoint or watchpoint
+    info                        list breakpoints and watchpoints
+    r, regs                     show registers
+    set <reg> <value>           set a register (a, f, b, c, d, e, h, l, af, bc, de, hl, sp, pc)
+    x <addr> [count]            show count bytes of memory (default 64)
+    poke <addr> <byte>...       write bytes to memory
+    l, list [addr] [count]      disassemble count instructions from addr (default PC)
+    hide, show <layer>          hide or show a layer in the renderer (bg, win or obj), or one object (obj <n>)
+    layers                      list hidden layers and objects
+    q, quit                     quit the emulator
+
+addresses and values are decimal, or hexadecimal with a $ or 0x prefix;
+ROM addresses can be qualified with a bank (e.g. 03:4000), and labels can be used as addresses
+conditions compare a register with a value, using ==, !=, <, <=, > or >=";
+
+#[derive(Clone, Copy)]
+enum Compare {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+const COMPARISONS: [(&str, Compare); 6] = [
+    ("==", Compare::Eq), ("!=", Compare::Ne), ("<=", Compare::Le),
+    (">=", Compare::Ge), ("<", Compare::Lt), (">", Compare::Gt),
+];
+
+/// Compares a register with a value, e.g. hl >= $C000.
+struct Condition {
+    register: String,
+    compare: Compare,
+    value: u16,
+    text: String,
+}
+
+impl Condition {
+    fn parse(text: &str) -> Result<Self, String> {
+        let (symbol, compare) = COMPARISONS.iter()
+            .find(|(symbol, _)| text.contains(symbol))
+            .ok_or_else(|| format!("invalid condition {}", text))?;
+        let (register, value) = text.split_once(symbol).unwrap();
+
+        let register = register.trim().to_lowercase();
+        if read_register(&Registers::default(), &register).is_none() {
+            return Err(format!("unknown register {}", register));
+        }
+
+        Ok(Condition {
+            register,
+            compare: *compare,
+            value: parse_u16(value.trim())?,
+            text: text.trim().to_string(),
+        })
+    }
+
+    fn holds(&self, registers: &Registers) -> bool {
+        let register = read_register(registers, &self.register).unwrap();
+        match self.compare {
+            Compare::Eq => register == self.value,
+            Compare::Ne => register != self.value,
+            Compare::Lt => register < self.value,
+            Compare::Le => register <= self.value,
+            Compare::Gt => register > self.value,
+            Compare::Ge => register >= self.value,
+        }
+    }
+}
+
+/// Breaks at an address (in a given ROM bank, if any) and/or when a condition holds.
+struct Breakpoint {
+    id: usize,
+    bank: Option<usize>,
+    addr: Option<u16>,
+    condition: Option<Condition>,
+}
+
+impl Breakpoint {
+    fn hit(&self, registers: &Registers, bank: usize) -> bool {
+        let at_addr = match self.addr {
+            Some(addr) => addr == registers.pc && self.bank.is_none_or(|b| b == bank),
+            None => true,
+        };
+        at_addr && self.condition.as_ref().is_none_or(|condition| condition.holds(registers))
+    }
+
+    fn display(&self, symbols: Option<&Symbols>) -> String {
+        let location = match self.addr {
+            Some(addr) => describe(addr, self.bank.unwrap_or(1), self.bank.is_some(), symbols),
+            None => String::from("any"),
+        };
+        match &self.condition {
+            Some(condition) => format!("{}: break {} if {}", self.id, location, condition.text),
+            None => format!("{}: break {}", self.id, location),
+        }
+    }
+}
+
+struct Watch {
+    id: usize,
+    start: u16,
+    end: u16,
+    read: bool,
+    write: bool,
+    execute: bool,
+}
+
+impl Watch {
+    fn display(&self) -> String {
+        let kinds: String = [(self.read, 'r'), (self.write, 'w'), (self.execute, 'x')].iter()
+            .filter(|(enabled, _)| *enabled)
+            .map(|(_, kind)| *kind)
+            .collect();
+        format!("{}: watch {:04X}-{:04X} {}", self.id, self.start, self.end, kinds)
+    }
+}
+
+enum Mode {
+    Paused,
+    Continue,
+    /// Pauses once the given number of instructions have been executed
+    Step(usize),
+    /// Pauses when PC returns to addr (with SP back to where it was)
+    StepOver { addr: u16, sp: u16 },
+    /// Pauses after a return pops SP above sp
+    StepOut { sp: u16 },
+}
+
+/// Interactive debugger for the native binary, which is checked before every CPU step.
+pub struct Debugger {
+    symbols: Option<Rc<Symbols>>,
+    breakpoints: Vec<Breakpoint>,
+    watches: Vec<Watch>,
+    next_id: usize,
+    mode: Mode,
+    history: VecDeque<u16>,
+    returning: bool,
+    last_command: String,
+    quit: bool,
+}
+
+impl Debugger {
+    /// Makes a debugger which pauses before the first instruction, using labels from symbols (if any).
+    pub fn new(symbols: Option<Rc<Symbols>>) -> Self {
+        Debugger {
+            symbols,
+            breakpoints: Vec::new(),
+            watches: Vec::new(),
+            next_id: 1,
+            mode: Mode::Paused,
+            history: VecDeque::with_capacity(HISTORY_LINES),
+            returning: false,
+            last_command: String::new(),
+            quit: false,
+        }
+    }
+
+    /// Pauses before the next instruction.
+    pub fn pause(&mut self) {
+        self.mode = Mode::Paused;
+    }
+
+    /// Checks for breakpoints, watchpoints and stepping before the CPU steps,
+    /// running the command prompt while paused; returns false once the user quits.
+    pub fn before_step(&mut self, cpu: &mut Cpu) -> bool {
+        let registers = cpu.registers();
+        let bank = cpu.rom_bank();
+
+        if let Some(hit) = cpu.take_watch_hit() {
+            self.report_watch_hit(hit, bank);
+            self.mode = Mode::Paused;
+        }
+        if cpu.take_debug_break() {
+            let by = self.history.back().map(|pc| describe(*pc, bank, true, self.symbols.as_deref()));
+            println!("hit LD B,B breakpoint at {}", by.unwrap_or_default());
+            self.mode = Mode::Paused;
+        }
+
+        match self.mode {
+            Mode::Step(n) if n <= 1 => self.mode = Mode::Paused,
+            Mode::Step(n) => self.mode = Mode::Step(n - 1),
+            Mode::StepOver { addr, sp } if registers.pc == addr && registers.sp >= sp => self.mode = Mode::Paused,
+            Mode::StepOut { sp } if self.returning && registers.sp > sp => self.mode = Mode::Paused,
+            _ => {}
+        }
+
+        // a halted CPU stays at the same PC, which would otherwise hit the same breakpoint again
+        if !registers.halted && !matches!(self.mode, Mode::Paused) {
+            if let Some(breakpoint) = self.breakpoints.iter().find(|b| b.hit(&registers, bank)) {
+                println!("hit breakpoint {}", breakpoint.display(self.symbols.as_deref()));
+                self.mode = Mode::Paused;
+            } else if let Some(watch) = self.watches.iter()
+                .find(|w| w.execute && (w.start..=w.end).contains(&registers.pc)) {
+                let location = describe(registers.pc, bank, true, self.symbols.as_deref());
+                println!("hit watchpoint {} (execute {})", watch.id, location);
+                self.mode = Mode::Paused;
+            }
+        }
+
+        if matches!(self.mode, Mode::Paused) {
+            self.show_location(cpu);
+            self.prompt(cpu);
+        }
+
+        let pc = cpu.registers().pc;
+        self.returning = matches!(cpu.peek_byte(pc), 0xC9 | 0xD9 | 0xC0 | 0xC8 | 0xD0 | 0xD8);
+        if !cpu.registers().halted {
+            if self.history.len() == HISTORY_LINES {
+                self.history.pop_front();
+            }
+            self.history.push_back(pc);
+        }
+
+        !self.quit
+    }
+
+    fn report_watch_hit(&self, hit: WatchHit, bank: usize) {
+        let symbols = self.symbols.as_deref();
+        let by = self.history.back().map(|pc| describe(*pc, bank, true, symbols)).unwrap_or_default();
+        let addr = describe(hit.addr, bank, false, symbols);
+        match hit.access {
+            Access::Read => println!("hit watchpoint {} (read ${:02X} from {} at {})", hit.id, hit.byte, addr, by),
+            Access::Write => println!("hit watchpoint {} (write ${:02X} to {} at {})", hit.id, hit.byte, addr, by),
+        }
+    }
+
+    /// Shows the registers and the disassembly around PC.
+    fn show_location(&self, cpu: &Cpu) {
+        println!("{}", display_registers(&cpu.registers()));
+
+        let bank = cpu.rom_bank();
+        let pc = cpu.registers().pc;
+        let symbols = self.symbols.as_deref();
+        for addr in &self.history {
+            println!("   {}", disassemble(|a| cpu.peek_byte(a), *addr, bank, symbols).display(bank));
+        }
+
+        let mut addr = pc;
+        for i in 0..UPCOMING_LINES {
+            let instruction = disassemble(|a| cpu.peek_byte(a), addr, bank, symbols);
+            if let Some(label) = &instruction.label {
+                println!("{}:", label);
+            }
+            let marker = if i == 0 { "=> " } else { "   " };
+            println!("{}{}", marker, instruction.display(bank));
+            addr = instruction.next_addr();
+        }
+    }
+
+    /// Reads and runs commands until one resumes execution (or the user quits).
+    fn prompt(&mut self, cpu: &mut Cpu) {
+        let stdin = io::stdin();
+        while matches!(self.mode, Mode::Paused) && !self.quit {
+            print!("(gbdb) ");
+            io::stdout().flush().unwrap();
+
+            let mut line = String::new();
+            if stdin.lock().read_line(&mut line).unwrap_or(0) == 0 {
+                self.quit = true;
+                return;
+            }
+
+            let line = match line.trim() {
+                "" => self.last_command.clone(),
+                line => line.to_string(),
+            };
+            if let Err(message) = self.run_command(&line, cpu) {
+                println!("{}", message);
+            }
+            self.last_command = line;
+        }
+    }
+
+    fn run_command(&mut self, line: &str, cpu: &mut Cpu) -> Result<(), String> {
+        let args: Vec<&str> = line.split_whitespace().collect();
+        let (command, args) = match args.split_first() {
+            Some((command, args)) => (*command, args),
+            None => return Ok(()),
+        };
+        let registers = cpu.registers();
+
+        match command {
+            "c" | "continue" => self.mode = Mode::Continue,
+            "s" | "step" => {
+                let n = args.first().map(|arg| parse_value(arg)).transpose()?.unwrap_or(1);
+                self.mode = Mode::Step(n.max(1));
+            }
+            "n" | "next" => {
+                let instruction = disassemble(|a| cpu.peek_byte(a), registers.pc, cpu.rom_bank(), None);
+                self.mode = if instruction.text.starts_with("CALL") || instruction.text.starts_with("RST") {
+                    Mode::StepOver { addr: instruction.next_addr(), sp: registers.sp }
+                } else {
+                    Mode::Step(1)
+                };
+            }
+            "finish" => self.mode = Mode::StepOut { sp: registers.sp },
+            "b" | "break" => self.add_breakpoint(&line[command.len()..])?,
+            "watch" => self.add_watch(args, cpu)?,
+            "d" | "delete" => {
+                let id = parse_value(args.first().ok_or("usage: delete <id>")?)?;
+                let count = self.breakpoints.len() + self.watches.len();
+                self.breakpoints.retain(|breakpoint| breakpoint.id != id);
+                self.watches.retain(|watch| watch.id != id);
+                if count == self.breakpoints.len() + self.watches.len() {
+                    return Err(format!("no breakpoint or watchpoint {}", id));
+                }
+                self.sync_watchpoints(cpu);
+            }
+            "info" => {
+                for breakpoint in &self.breakpoints {
+                    println!("{}", breakpoint.display(self.symbols.as_deref()));
+                }
+                for watch in &self.watches {
+                    println!("{}", watch.display());
+                }
+            }
+            "r" | "regs" => println!("{}", display_registers(&registers)),
+            "set" => {
+                let [register, value] = args else { return Err(String::from("usage: set <reg> <value>")) };
+                let mut registers = registers;
+                write_register(&mut registers, &register.to_lowercase(), parse_u16(value)?)?;
+                cpu.set_registers(registers);
+            }
+            "x" => {
+                let addr = self.parse_location(args.first().ok_or("usage: x <addr> [count]")?)?.1;
+                let count = args.get(1).map(|arg| parse_value(arg)).transpose()?.unwrap_or(64);
+                for row in (0..count).step_by(16) {
+                    let row_addr = addr.wrapping_add(row as u16);
+                    let bytes: Vec<String> = (row..count.min(row + 16))
+                        .map(|i| format!("{:02X}", cpu.peek_byte(addr.wrapping_add(i as u16))))
+                        .collect();
+                    println!("{:04X}  {}", row_addr, bytes.join(" "));
+                }
+            }
+            "poke" => {
+                let (addr, bytes) = args.split_first().ok_or("usage: poke <addr> <byte>...")?;
+                let addr = self.parse_location(addr)?.1;
+                for (i, byte) in bytes.iter().enumerate() {
+                    cpu.bus_mut().write_byte(addr.wrapping_add(i as u16), parse_u8(byte)?);
+                }
+                // edits are not accesses by the CPU
+                cpu.take_watch_hit();
+            }
+            "l" | "list" => {
+                let addr = match args.first() {
+                    Some(arg) => self.parse_location(arg)?.1,
+                    None => registers.pc,
+                };
+                let count = args.get(1).map(|arg| parse_value(arg)).transpose()?.unwrap_or(16);
+                let bank = cpu.rom_bank();
+                let mut addr = addr;
+                for _ in 0..count {
+                    let instruction = disassemble(|a| cpu.peek_byte(a), addr, bank, self.symbols.as_deref());
+                    if let Some(label) = &instruction.label {
+                        println!("{}:", label);
+                    }
+                    println!("   {}", instruction.display(bank));
+                    addr = instruction.next_addr();
+                }
+            }
+            "hide" | "show" => {
+                let ppu = cpu.bus_mut().ppu_mut();
+                match args {
+                    ["obj", index] => {
+                        let index = parse_value(index)?;
+                        if !ppu.set_object_enabled(index, command == "show") {
+                            return Err(format!("no OAM entry {} (0-39)", index));
+                        }
+                    }
+                    [layer] => ppu.set_layer_enabled(parse_layer(layer)?, command == "show"),
+                    _ => return Err(format!("usage: {} bg|win|obj|obj <n>", command)),
+                }
+            }
+            "layers" => {
+                let ppu = cpu.bus().ppu();
+                for layer in [Layer::Background, Layer::Window, Layer::Objects] {
+                    println!("{:?}: {}", layer, if ppu.layer_enabled(layer) { "shown" } else { "hidden" });
+                }
+                let hidden: Vec<String> = (0..40).filter(|i| !ppu.object_enabled(*i)).map(|i| i.to_string()).collect();
+                if !hidden.is_empty() {
+                    println!("hidden objects: {}", hidden.join(" "));
+                }
+            }
+            "q" | "quit" => self.quit = true,
+            "h" | "help" => println!("{}", HELP),
+            _ => return Err(format!("unknown command {} (see help)", command)),
+        }
+
+        Ok(())
+    }
+
+    /// Adds a breakpoint from '<addr> [if <cond>]' or 'if <cond>'.
+    fn add_breakpoint(&mut self, args: &str) -> Result<(), String> {
+        let args = args.trim();
+        // "if" is a separate word, so labels containing it are not split
+        let split = args.strip_prefix("if ").map(|condition| ("", condition))
+            .or_else(|| args.split_once(" if "));
+        let (location, condition) = match split {
+            Some((location, condition)) => (location.trim(), Some(Condition::parse(condition)?)),
+            None => (args, None),
+        };
+
+        let (bank, addr) = match location {
+            "" if condition.is_none() => return Err(String::from("usage: break <addr> [if <cond>]")),
+            "" => (None, None),
+            location => {
+                let (bank, addr) = self.parse_location(location)?;
+                (bank, Some(addr))
+            }
+        };
+
+        let breakpoint = Breakpoint { id: self.next_id, bank, addr, condition };
+        println!("added {}", breakpoint.display(self.symbols.as_deref()));
+        self.breakpoints.push(breakpoint);
+        self.next_id += 1;
+        Ok(())
+    }
+
+    fn add_watch(&mut self, args: &[&str], cpu: &mut Cpu) -> Result<(), String> {
+        let range = args.first().ok_or("usage: watch <addr>[-<end>] [rwx]")?;
+        let (start, end) = match range.split_once('-') {
+            Some((start, end)) => (self.parse_location(start)?.1, self.parse_location(end)?.1),
+            None => (self.parse_location(range)?.1, self.parse_location(range)?.1),
+        };
+        let kinds = args.get(1).copied().unwrap_or("w");
+        if start > end || kinds.is_empty() || !kinds.chars().all(|c| "rwx".contains(c)) {
+            return Err(String::from("usage: watch <addr>[-<end>] [rwx]"));
+        }
+
+        let watch = Watch {
+            id: self.next_id,
+            start,
+            end,
+            read: kinds.contains('r'),
+            write: kinds.contains('w'),
+            execute: kinds.contains('x'),
+        };
+        println!("added {}", watch.display());
+        self.watches.push(watch);
+        self.next_id += 1;
+        self.sync_watchpoints(cpu);
+        Ok(())
+    }
+
+    /// Parses an address or a label; labels in switchable ROM banks are qualified with their bank.
+    fn parse_location(&self, arg: &str) -> Result<(Option<usize>, u16), String> {
+        match self.symbols.as_ref().and_then(|symbols| symbols.lookup(arg)) {
+            Some((bank, addr @ 0x4000..=0x7FFF)) => Ok((Some(bank), addr)),
+            Some((_, addr)) => Ok((None, addr)),
+            None => parse_location(arg),
+        }
+    }
+
+    /// Hands the read/write watchpoints to the bus, which checks every access (if there are any).
+    fn sync_watchpoints(&self, cpu: &mut Cpu) {
+        let mut watchpoints = Watchpoints::new();
+        for watch in self.watches.iter().filter(|watch| watch.read || watch.write) {
+            watchpoints.add(watch.id, watch.start, watch.end, watch.read, watch.write);
+        }
+
+        cpu.set_watchpoints(if watchpoints.is_empty() { None } else { Some(watchpoints) });
+    }
+}
+
+/// Parses a decimal number, or a hexadecimal number with a $ or 0x prefix.
+fn parse_value(arg: &str) -> Result<usize, String> {
+    match arg.strip_prefix('$') {
+        Some(hex) => usize::from_str_radix(hex, 16).map_err(|_| format!("invalid number {}", arg)),
+        None => number(arg),
+    }
+}
+
+/// Parses a 16-bit value (see parse_value), e.g. an address.
+fn parse_u16(arg: &str) -> Result<u16, String> {
+    u16::try_from(parse_value(arg)?).map_err(|_| format!("{} is out of range (0-$FFFF)", arg))
+}
+
+/// Parses a byte value (see parse_value).
+fn parse_u8(arg: &str) -> Result<u8, String> {
+    u8::try_from(parse_value(arg)?).map_err(|_| format!("{} is out of range (0-$FF)", arg))
+}
+
+/// Parses a renderer layer by name (bg, win or obj).
+fn parse_layer(arg: &str) -> Result<Layer, String> {
+    match arg {
+        "bg" => Ok(Layer::Background),
+        "win" | "window" => Ok(Layer::Window),
+        "obj" | "objects" => Ok(Layer::Objects),
+        _ => Err(format!("unknown layer {} (expected bg, win or obj)", arg)),
+    }
+}
+
+/// Parses an address, optionally qualified by a ROM bank as in the disassembly (e.g. 03:4000, in hex).
+fn parse_location(arg: &str) -> Result<(Option<usize>, u16), String> {
+    let (bank, addr) = match arg.split_once(':') {
+        Some((bank, addr)) => {
+            let bank = usize::from_str_radix(bank, 16).map_err(|_| format!("invalid bank {}", bank))?;
+            let addr = u16::from_str_radix(addr, 16).map_err(|_| format!("invalid address {}", addr))?;
+            (Some(bank), addr)
+        }
+        None => (None, parse_u16(arg)?),
+    };
+
+    match (bank, addr) {
+        // bank 0 is always mapped to 0x0000-0x3FFF
+        (Some(0), 0x0000..=0x3FFF) => Ok((None, addr)),
+        (Some(_), 0x4000..=0x7FFF) | (None, _) => Ok((bank, addr)),
+        (Some(_), _) => Err(format!("{} is not in a switchable ROM bank (0x4000-0x7FFF)", arg)),
+    }
+}
+
+/// Describes an address as its closest label (if any), qualified with the bank if in_rom_bank.
+fn describe(addr: u16, bank: usize, in_rom_bank: bool, symbols: Option<&Symbols>) -> String {
+    let location = if in_rom_bank { format_addr(addr, bank).trim().to_string() } else { format!("{:04X}", addr) };
+    match symbols.and_then(|symbols| symbols.describe(addr, bank)) {
+        Some(label) => format!("{} ({})", location, label),
+        None => location,
+    }
+}
+
+fn read_register(registers: &Registers, name: &str) -> Option<u16> {
+    let value = match name {
+        "a" => registers.af >> 8,
+        "f" => registers.af & 0xFF,
+        "b" => registers.bc >> 8,
+        "c" => registers.bc & 0xFF,
+        "d" => registers.de >> 8,
+        "e" => registers.de & 0xFF,
+        "h" => registers.hl >> 8,
+        "l" => registers.hl & 0xFF,
+        "af" => registers.af,
+        "bc" => registers.bc,
+        "de" => registers.de,
+        "hl" => registers.hl,
+        "sp" => registers.sp,
+        "pc" => registers.pc,
+        _ => return None,
+    };
+    Some(value)
+}
+
+fn write_register(registers: &mut Registers, name: &str, value: u16) -> Result<(), String> {
+    let hi = |pair: u16| (pair & 0x00FF) | (value & 0xFF) << 8;
+    let lo = |pair: u16| (pair & 0xFF00) | (value & 0xFF);
+    // single letters are 8-bit registers
+    if name.len() == 1 && value > 0xFF {
+        return Err(format!("${:X} is out of range for register {} (0-$FF)", value, name));
+    }
+    match name {
+        "a" => registers.af = hi(registers.af),
+        "f" => registers.af = lo(registers.af),
+        "b" => registers.bc = hi(registers.bc),
+        "c" => registers.bc = lo(registers.bc),
+        "d" => registers.de = hi(registers.de),
+        "e" => registers.de = lo(registers.de),
+        "h" => registers.hl = hi(registers.hl),
+        "l" => registers.hl = lo(registers.hl),
+        "af" => registers.af = value,
+        "bc" => registers.bc = value,
+        "de" => registers.de = value,
+        "hl" => registers.hl = value,
+        "sp" => registers.sp = value,
+        "pc" => registers.pc = value,
+        _ => return Err(format!("unknown register {}", name)),
+    }
+    Ok(())
+}
+
+fn display_registers(registers: &Registers) -> String {
+    let flags: String = [(7, 'Z'), (6, 'N'), (5, 'H'), (4, 'C')].iter()
+        .map(|(bit, flag)| if registers.af & (1 << bit) != 0 { *flag } else { '-' })
+        .collect();
+
+    format!("AF={:04X} BC={:04X} DE={:04X} HL={:04X} SP={:04X} PC={:04X}  {}  IME={}{}",
+        registers.af, registers.bc, registers.de, registers.hl, registers.sp, registers.pc,
+        flags, registers.ime as u8, if registers.halted { "  (halted)" } else { "" })
+}
@@ -0,0 +1,246 @@
+use crate::symbols::Symbols;
+use crate::cpu::{ACC_NAMES, ALU_NAMES, CB_NAMES, CC_NAMES, R16_NAMES, R16_STACK_NAMES, R8_NAMES};
+
+const ROM_BANK_SIZE: usize = 0x4000;
+
+/// A decoded SM83 instruction.
+pub struct Instruction {
+    pub addr: u16,
+    pub bytes: Vec<u8>,
+    pub text: String,
+    /// Label at addr (if symbols were given)
+    pub label: Option<String>,
+    /// Absolute target of jumps, calls and RSTs
+    pub target: Option<u16>,
+}
+
+impl Instruction {
+    pub fn size(&self) -> u16 {
+        self.bytes.len() as u16
+    }
+
+    /// Returns the address of the instruction after this one.
+    pub fn next_addr(&self) -> u16 {
+        self.addr.wrapping_add(self.size())
+    }
+
+    /// Formats as a listing line (bank-qualified address, bytes, then text);
+    /// bank is the ROM bank mapped to 0x4000-0x7FFF.
+    pub fn display(&self, bank: usize) -> String {
+        let bytes: Vec<String> = self.bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+        format!("{}  {:<8}  {}", format_addr(self.addr, bank), bytes.join(" "), self.text)
+    }
+}
+
+/// Formats an address, qualifying ROM addresses with their bank (e.g. 03:4000);
+/// bank is the ROM bank mapped to 0x4000-0x7FFF.
+pub fn format_addr(addr: u16, bank: usize) -> String {
+    match addr {
+        0x0000..=0x3FFF => format!("00:{:04X}", addr),
+        0x4000..=0x7FFF => format!("{:02X}:{:04X}", bank, addr),
+        _ => format!("   {:04X}", addr),
+    }
+}
+
+/// Formats an absolute address operand as its label if there is one.
+fn format_target(addr: u16, bank: usize, symbols: Option<&Symbols>) -> String {
+    match symbols.and_then(|symbols| symbols.label(addr, bank)) {
+        Some(label) => label.to_string(),
+        None => format_addr(addr, bank).trim_start().to_string(),
+    }
+}
+
+/// Formats a memory operand (e.g. of LD (a16), A) as its label if there is one.
+fn format_pointer(addr: u16, bank: usize, symbols: Option<&Symbols>) -> String {
+    match symbols.and_then(|symbols| symbols.label(addr, bank)) {
+        Some(label) => label.to_string(),
+        None => format!("${:04X}", addr),
+    }
+}
+
+/// Decodes the instruction at addr, reading memory with read; bank is the ROM bank
+/// mapped to 0x4000-0x7FFF (for formatting jump targets, and looking up labels in symbols).
+pub fn disassemble(read: impl Fn(u16) -> u8, addr: u16, bank: usize, symbols: Option<&Symbols>) -> Instruction {
+    let mut bytes = vec![read(addr)];
+    let operand = |bytes: &mut Vec<u8>| {
+        let byte = read(addr.wrapping_add(bytes.len() as u16));
+        bytes.push(byte);
+        byte
+    };
+
+    let opcode = bytes[0];
+    let (x, y, z) = (opcode >> 6, ((opcode >> 3) & 7) as usize, (opcode & 7) as usize);
+    let (p, q) = (y >> 1, y & 1);
+
+    let mut target = None;
+    let text = match opcode {
+        0x00 => String::from("NOP"),
+        0x08 => {
+            let n16 = u16::from_le_bytes([operand(&mut bytes), operand(&mut bytes)]);
+            format!("LD ({}), SP", format_pointer(n16, bank, symbols))
+        }
+        0x10 => {
+            operand(&mut bytes);
+            String::from("STOP")
+        }
+        0x18 | 0x20 | 0x28 | 0x30 | 0x38 => {
+            let e8 = operand(&mut bytes) as i8;
+            let dest = addr.wrapping_add(2).wrapping_add(e8 as u16);
+            target = Some(dest);
+            if opcode == 0x18 {
+                format!("JR {}", format_target(dest, bank, symbols))
+            } else {
+                format!("JR {}, {}", CC_NAMES[y - 4], format_target(dest, bank, symbols))
+            }
+        }
+        0x76 => String::from("HALT"),
+        0xCB => {
+            let cb = operand(&mut bytes);
+            let (cb_x, cb_y, cb_z) = (cb >> 6, (cb >> 3) & 7, (cb & 7) as usize);
+            match cb_x {
+                0 => format!("{} {}", CB_NAMES[cb_y as usize], R8_NAMES[cb_z]),
+                1 => format!("BIT {}, {}", cb_y, R8_NAMES[cb_z]),
+                2 => format!("RES {}, {}", cb_y, R8_NAMES[cb_z]),
+                _ => format!("SET {}, {}", cb_y, R8_NAMES[cb_z]),
+            }
+        }
+
+        _ if x == 0 => match z {
+            1 if q == 0 => {
+                let n16 = u16::from_le_bytes([operand(&mut bytes), operand(&mut bytes)]);
+                format!("LD {}, ${:04X}", R16_NAMES[p], n16)
+            }
+            1 => format!("ADD HL, {}", R16_NAMES[p]),
+            2 => {
+                let indirect = ["(BC)", "(DE)", "(HL+)", "(HL-)"][p];
+                if q == 0 { format!("LD {}, A", indirect) } else { format!("LD A, {}", indirect) }
+            }
+            3 if q == 0 => format!("INC {}", R16_NAMES[p]),
+            3 => format!("DEC {}", R16_NAMES[p]),
+            4 => format!("INC {}", R8_NAMES[y]),
+            5 => format!("DEC {}", R8_NAMES[y]),
+            6 => format!("LD {}, ${:02X}", R8_NAMES[y], operand(&mut bytes)),
+            _ => String::from(ACC_NAMES[y]),
+        }
+        _ if x == 1 => format!("LD {}, {}", R8_NAMES[y], R8_NAMES[z]),
+        _ if x == 2 => format!("{} {}", ALU_NAMES[y], R8_NAMES[z]),
+
+        0xE0 => format!("LDH ($FF{:02X}), A", operand(&mut bytes)),
+        0xF0 => format!("LDH A, ($FF{:02X})", operand(&mut bytes)),
+        0xE8 => format!("ADD SP, {}", operand(&mut bytes) as i8),
+        0xF8 => format!("LD HL, SP{:+}", operand(&mut bytes) as i8),
+        0xC9 => String::from("RET"),
+        0xD9 => String::from("RETI"),
+        0xE9 => String::from("JP HL"),
+        0xF9 => String::from("LD SP, HL"),
+        0xE2 => String::from("LD ($FF00+C), A"),
+        0xF2 => String::from("LD A, ($FF00+C)"),
+        0xEA | 0xFA => {
+            let n16 = u16::from_le_bytes([operand(&mut bytes), operand(&mut bytes)]);
+            let pointer = format_pointer(n16, bank, symbols);
+            if opcode == 0xEA { format!("LD ({}), A", pointer) } else { format!("LD A, ({})", pointer) }
+        }
+        0xF3 => String::from("DI"),
+        0xFB => String::from("EI"),
+        0xC3 | 0xCD | 0xC2 | 0xCA | 0xD2 | 0xDA | 0xC4 | 0xCC | 0xD4 | 0xDC => {
+            let n16 = u16::from_le_bytes([operand(&mut bytes), operand(&mut bytes)]);
+            target = Some(n16);
+            let mnemonic = if z == 2 || opcode == 0xC3 { "JP" } else { "CALL" };
+            if opcode == 0xC3 || opcode == 0xCD {
+                format!("{} {}", mnemonic, format_target(n16, bank, symbols))
+            } else {
+                format!("{} {}, {}", mnemonic, CC_NAMES[y], format_target(n16, bank, symbols))
+            }
+        }
+
+        _ => match z {
+            0 if y < 4 => format!("RET {}", CC_NAMES[y]),
+            1 if q == 0 => format!("POP {}", R16_STACK_NAMES[p]),
+            5 if q == 0 => format!("PUSH {}", R16_STACK_NAMES[p]),
+            6 => format!("{} ${:02X}", ALU_NAMES[y], operand(&mut bytes)),
+            7 => {
+                target = Some(y as u16 * 8);
+                format!("RST ${:02X}", y * 8)
+            }
+            // unused opcodes (0xD3, 0xDB, 0xDD, 0xE3, 0xE4, 0xEB-0xED, 0xF4, 0xFC, 0xFD)
+            _ => format!("DB ${:02X}", opcode),
+        }
+    };
+
+    let label = symbols.and_then(|symbols| symbols.label(addr, bank)).map(String::from);
+    Instruction { addr, bytes, text, label, target }
+}
+
+/// Disassembles count instructions from a ROM file's contents, starting at from
+/// with the given bank mapped to 0x4000-0x7FFF; returns one listing line per instruction
+/// (and one per label, if symbols are given).
+pub fn disassemble_rom(rom: &[u8], bank: usize, from: u16, count: usize, symbols: Option<&Symbols>) -> Vec<String> {
+    let read = |addr: u16| {
+        let offset = match addr {
+            0x0000..=0x3FFF => addr as usize,
+            0x4000..=0x7FFF => bank * ROM_BANK_SIZE + (addr as usize - ROM_BANK_SIZE),
+            _ => return 0xFF,
+        };
+        rom.get(offset).copied().unwrap_or(0xFF)
+    };
+
+    let mut addr = from;
+    let mut lines = Vec::with_capacity(count);
+    for _ in 0..count {
+        let instruction = disassemble(read, addr, bank, symbols);
+        if let Some(label) = &instruction.label {
+            lines.push(format!("{}:", label));
+        }
+        lines.push(instruction.display(bank));
+
+        addr = instruction.next_addr();
+        if addr < from {
+            break;
+        }
+    }
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::disassemble;
+    use crate::symbols::Symbols;
+
+    fn disassemble_bytes(bytes: &[u8], addr: u16, bank: usize) -> (String, u16) {
+        let read = |a: u16| bytes.get(a.wrapping_sub(addr) as usize).copied().unwrap_or(0);
+        let instruction = disassemble(read, addr, bank, None);
+        (instruction.text.clone(), instruction.size())
+    }
+
+    #[test]
+    fn disasm_test() {
+        assert_eq!(disassemble_bytes(&[0x00], 0x0100, 1), (String::from("NOP"), 1));
+        assert_eq!(disassemble_bytes(&[0xC3, 0x50, 0x01], 0x0100, 1), (String::from("JP 00:0150"), 3));
+        assert_eq!(disassemble_bytes(&[0xCD, 0x00, 0x40], 0x0100, 3), (String::from("CALL 03:4000"), 3));
+        assert_eq!(disassemble_bytes(&[0x20, 0xFE], 0x4010, 2), (String::from("JR NZ, 02:4010"), 2));
+        assert_eq!(disassemble_bytes(&[0x2A], 0xC000, 1), (String::from("LD A, (HL+)"), 1));
+        assert_eq!(disassemble_bytes(&[0x36, 0x12], 0xC000, 1), (String::from("LD (HL), $12"), 2));
+        assert_eq!(disassemble_bytes(&[0x9E], 0xC000, 1), (String::from("SBC A, (HL)"), 1));
+        assert_eq!(disassemble_bytes(&[0xE0, 0x44], 0xC000, 1), (String::from("LDH ($FF44), A"), 2));
+        assert_eq!(disassemble_bytes(&[0xF8, 0xFE], 0xC000, 1), (String::from("LD HL, SP-2"), 2));
+        assert_eq!(disassemble_bytes(&[0xCB, 0x7C], 0xC000, 1), (String::from("BIT 7, H"), 2));
+        assert_eq!(disassemble_bytes(&[0xCB, 0x36], 0xC000, 1), (String::from("SWAP (HL)"), 2));
+        assert_eq!(disassemble_bytes(&[0xF5], 0xC000, 1), (String::from("PUSH AF"), 1));
+        assert_eq!(disassemble_bytes(&[0xFF], 0xC000, 1), (String::from("RST $38"), 1));
+        assert_eq!(disassemble_bytes(&[0xD3], 0xC000, 1), (String::from("DB $D3"), 1));
+    }
+
+    #[test]
+    fn disasm_symbols_test() {
+        let symbols = Symbols::parse("00:0150 Main\n03:4000 PlayerUpdate\n00:c000 wPlayerX\n");
+        let bytes = [0xCD, 0x00, 0x40, 0xEA, 0x00, 0xC0];
+        let read = |a: u16| bytes.get(a.wrapping_sub(0x0150) as usize).copied().unwrap_or(0);
+
+        let call = disassemble(read, 0x0150, 3, Some(&symbols));
+        assert_eq!(call.label.as_deref(), Some("Main"));
+        assert_eq!(call.text, "CALL PlayerUpdate");
+        assert_eq!(disassemble(read, 0x0150, 4, Some(&symbols)).text, "CALL 04:4000");
+        assert_eq!(disassemble(read, 0x0153, 3, Some(&symbols)).text, "LD (wPlayerX), A");
+    }
+}
@@ -1,3 +1,7 @@
+use std::collections::HashSet;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::rc::Rc;
 use std::sync::mpsc::{Receiver, SyncSender};
 use std::time::Duration;
 
@@ -13,8 +17,17 @@ use sdl2::keyboard::Keycode;
 use sdl2::EventPump;
 
 use crate::cartridge::Cartridge;
-use crate::cpu::{Cpu, GBModel};
-use crate::config::{AUDIO_SAMPLES, SAMPLING_RATE_HZ};
+use crate::cpu::{CodeDataLog, Cpu, EventLog, ExceptionKind, ExceptionMode, FRAME_DOTS, FRAME_LINES, GBModel, Tracer};
+use crate::config::{AUDIO_SAMPLES, DMG_PALETTE, SAMPLING_RATE_HZ};
+use crate::debugger::Debugger;
+use crate::disasm::{disassemble, format_addr};
+use crate::gdb::GdbStub;
+use crate::image::save_png_rgb;
+use crate::palette::{ColourCorrection, DmgPalette, Rgb555Palette, PRESETS};
+use crate::ppu::Layer;
+use crate::profiler::Profiler;
+use crate::symbols::Symbols;
+use crate::vram;
 
 // in order of: START, SELECT, B, A, DOWN, UP, LEFT, RIGHT.
 pub const KEYMAPPINGS: [Keycode; 8] = [
@@ -28,17 +41,59 @@ pub const KEYMAPPINGS: [Keycode; 8] = [
     Keycode::D,
 ];
 
+// pauses in the debugger (if attached)
+pub const DEBUGGER_KEY: Keycode = Keycode::F12;
+
+// toggle the BG, window and object layers in the renderer
+pub const LAYER_KEYS: [(Keycode, Layer); 3] = [
+    (Keycode::F1, Layer::Background),
+    (Keycode::F2, Layer::Window),
+    (Keycode::F3, Layer::Objects),
+];
+
+// cycles through the DMG palettes
+pub const PALETTE_KEY: Keycode = Keycode::F4;
+
+// saves the VRAM viewer's images (see vram::dump)
+pub const VRAM_DUMP_KEY: Keycode = Keycode::F10;
+const VRAM_DUMP_DIR: &str = "vram";
+
 pub const SCREEN_SCALE: i32 = 5;
 
 pub const MASTER_VOLUME: f32 = 0.2;
 
 const PIXEL_FORMAT: PixelFormatEnum = PixelFormatEnum::ARGB8888;
 
+/// A DMG palette PALETTE_KEY cycles through: display colours, or CGB colours (see Ppu::set_rgb555_dmg_palette).
+#[derive(Clone, Copy)]
+enum PaletteChoice {
+    Display(DmgPalette),
+    Rgb555(Rgb555Palette),
+}
+
 pub struct Emulator {
     event_pump: EventPump,
     canvas: Canvas<Window>,
     key_status: u8,
     cpu: Cpu,
+    debugger: Option<Debugger>,
+    gdb: Option<GdbStub>,
+    profiler: Option<Profiler>,
+    // where the code/data log is saved (if enabled)
+    cdl_path: Option<PathBuf>,
+    // where the event log (JSON) and its overlay (PNG) are saved (if enabled)
+    events_path: Option<String>,
+    events_overlay_path: Option<String>,
+    // where VRAM_DUMP_KEY saves VRAM images, and whether they are also saved when the session ends
+    vram_dump_dir: String,
+    vram_dump_on_exit: bool,
+    // DMG palettes PALETTE_KEY cycles through (the presets, then any custom palette), and the one in use
+    palettes: Vec<(String, PaletteChoice)>,
+    palette_index: usize,
+    symbols: Option<Rc<Symbols>>,
+    exception_mode: Option<ExceptionMode>,
+    // exceptions already logged, by kind, address and PC
+    logged_exceptions: HashSet<(ExceptionKind, u16, u16)>,
     _audio_subsystem: AudioSubsystem,
     _audio_device: AudioDevice<Callback>,
     audio_tx: SyncSender<[[f32; 2]; AUDIO_SAMPLES]>
@@ -77,12 +132,146 @@ impl Emulator {
             canvas,
             key_status: 0xFF,
             cpu: Cpu::new(cartridge, model),
+            debugger: None,
+            gdb: None,
+            profiler: None,
+            cdl_path: None,
+            events_path: None,
+            events_overlay_path: None,
+            vram_dump_dir: String::from(VRAM_DUMP_DIR),
+            vram_dump_on_exit: false,
+            palettes: PRESETS.iter()
+                .map(|(name, shades)| (name.to_string(), PaletteChoice::Display(DmgPalette::uniform(*shades))))
+                .collect(),
+            palette_index: PRESETS.iter().position(|(name, _)| name.eq_ignore_ascii_case(DMG_PALETTE)).unwrap_or(0),
+            symbols: None,
+            exception_mode: None,
+            logged_exceptions: HashSet::new(),
             _audio_device,
             _audio_subsystem,
             audio_tx,
         })
     }
 
+    pub fn set_tracer(&mut self, tracer: Tracer) {
+        self.cpu.set_tracer(tracer);
+    }
+
+    pub fn set_ly_stub(&mut self, enabled: bool) {
+        self.cpu.set_ly_stub(enabled);
+    }
+
+    /// Draws every sprite on a line, not just the first 10 (see Ppu::set_unlimited_objects).
+    pub fn set_unlimited_sprites(&mut self, enabled: bool) {
+        self.cpu.bus_mut().ppu_mut().set_unlimited_objects(enabled);
+    }
+
+    /// Sets how CGB colours are displayed (see ColourCorrection::apply).
+    pub fn set_colour_correction(&mut self, correction: ColourCorrection, brightness: f32) {
+        self.cpu.bus_mut().ppu_mut().set_colour_correction(correction, brightness);
+    }
+
+    /// Uses palette for DMG games: replaces the preset with the same name (ignoring case),
+    /// or adds it to the palettes PALETTE_KEY cycles through.
+    pub fn set_dmg_palette(&mut self, name: &str, palette: DmgPalette) {
+        self.select_palette(name, PaletteChoice::Display(palette));
+    }
+
+    /// Uses a palette of CGB colours for DMG games (e.g. from colourization), which are
+    /// displayed with the colour correction; added like set_dmg_palette.
+    pub fn set_rgb555_dmg_palette(&mut self, name: &str, palette: Rgb555Palette) {
+        self.select_palette(name, PaletteChoice::Rgb555(palette));
+    }
+
+    fn select_palette(&mut self, name: &str, palette: PaletteChoice) {
+        self.palette_index = match self.palettes.iter().position(|(preset, _)| preset.eq_ignore_ascii_case(name)) {
+            Some(index) => {
+                self.palettes[index].1 = palette;
+                index
+            }
+            None => {
+                self.palettes.push((name.to_string(), palette));
+                self.palettes.len() - 1
+            }
+        };
+        self.use_palette(palette);
+    }
+
+    /// Switches to the next DMG palette (on PALETTE_KEY).
+    fn next_dmg_palette(&mut self) {
+        self.palette_index = (self.palette_index + 1) % self.palettes.len();
+        let palette = self.palettes[self.palette_index].1;
+        self.use_palette(palette);
+        println!("DMG palette: {}", self.palettes[self.palette_index].0);
+    }
+
+    fn use_palette(&mut self, palette: PaletteChoice) {
+        let ppu = self.cpu.bus_mut().ppu_mut();
+        match palette {
+            PaletteChoice::Display(palette) => ppu.set_dmg_palette(palette),
+            PaletteChoice::Rgb555(palette) => ppu.set_rgb555_dmg_palette(palette),
+        }
+    }
+
+    /// Enables LD B,B breakpoints and LD D,D debug messages (see Cpu::set_debug_hooks).
+    pub fn set_debug_hooks(&mut self, enabled: bool) {
+        self.cpu.set_debug_hooks(enabled);
+    }
+
+    /// Attaches a debugger, which is checked before every CPU step.
+    pub fn set_debugger(&mut self, debugger: Debugger) {
+        self.debugger = Some(debugger);
+    }
+
+    /// Uses labels from symbols in crash reports.
+    pub fn set_symbols(&mut self, symbols: Rc<Symbols>) {
+        self.symbols = Some(symbols);
+    }
+
+    /// Reports exceptions (e.g. reads of uninitialized RAM), either logging each one once
+    /// or breaking into the attached debugger (or GDB) every time.
+    pub fn set_exception_mode(&mut self, mode: ExceptionMode) {
+        self.cpu.set_exceptions(true);
+        self.exception_mode = Some(mode);
+    }
+
+    /// Attaches a GDB stub, which is checked before every CPU step.
+    pub fn set_gdb(&mut self, gdb: GdbStub) {
+        self.gdb = Some(gdb);
+    }
+
+    /// Attaches a profiler, which is given the cycles of every CPU step and reports when the session ends.
+    pub fn set_profiler(&mut self, profiler: Profiler) {
+        self.profiler = Some(profiler);
+    }
+
+    /// Logs ROM reads into log (see CodeDataLog), which is saved to path when the session ends.
+    pub fn set_code_data_log(&mut self, log: CodeDataLog, path: PathBuf) {
+        self.cpu.set_code_data_log(Some(log));
+        self.cdl_path = Some(path);
+    }
+
+    /// Records events (see EventLog), which are saved as JSON to json_path and/or
+    /// drawn as an overlay PNG to overlay_path when the session ends.
+    pub fn set_event_log(&mut self, json_path: Option<String>, overlay_path: Option<String>) {
+        self.cpu.set_event_log(Some(EventLog::new(overlay_path.is_some())));
+        self.events_path = json_path;
+        self.events_overlay_path = overlay_path;
+    }
+
+    /// Saves VRAM images (see vram::dump) into dir when the session ends, and on VRAM_DUMP_KEY.
+    pub fn set_vram_dump_dir(&mut self, dir: &str) {
+        self.vram_dump_dir = dir.to_string();
+        self.vram_dump_on_exit = true;
+    }
+
+    fn dump_vram(&self) {
+        match vram::dump(self.cpu.bus().ppu(), &self.vram_dump_dir) {
+            Ok(()) => println!("Saved VRAM images to {}", self.vram_dump_dir),
+            Err(e) => println!("Could not save VRAM images to {}: {}", self.vram_dump_dir, e),
+        }
+    }
+
     fn build_canvas(sdl_context: &Sdl, scale: u32, title: &str) -> Result<Canvas<Window>, String> {
         let video_subsystem = sdl_context.video()?;
         let window_width = LCD_WIDTH as u32 * scale;
@@ -122,13 +311,127 @@ impl Emulator {
         let rect = Rect::new(0, 0, screen_width, screen_height);
 
         // NOTE: cycle timings seem to be controlled by APU audio callback 
-        while dur_ns < total_dur_ns {
+        while dur_ns < total_dur_ns && !self.cpu.trace_stopped() {
+            if let Some(debugger) = &mut self.debugger {
+                if !debugger.before_step(&mut self.cpu) {
+                    self.cpu.save_mbc_state();
+                    break;
+                }
+            }
+            if let Some(gdb) = &mut self.gdb {
+                if !gdb.before_step(&mut self.cpu) {
+                    self.cpu.save_mbc_state();
+                    break;
+                }
+            }
+
             self.cpu.update_joypad(self.key_status);
-            let t_cycles = self.cpu.step() as u64;
+            let pc = self.cpu.registers().pc;
+            // the instruction may switch banks, so pc is described in the bank it was fetched from
+            let bank = self.cpu.rom_bank();
+            if let Some(profiler) = &mut self.profiler {
+                profiler.before_step(&self.cpu);
+            }
+            let t_cycles = match panic::catch_unwind(AssertUnwindSafe(|| self.cpu.step())) {
+                Ok(t_cycles) => t_cycles as u64,
+                Err(payload) => {
+                    self.report_crash(pc, bank);
+                    self.cpu.save_mbc_state();
+                    self.finish_session();
+                    panic::resume_unwind(payload);
+                }
+            };
+            if let Some(profiler) = &mut self.profiler {
+                profiler.after_step(&self.cpu, t_cycles as u32);
+            }
+            if let Some(mode) = self.exception_mode {
+                self.report_exceptions(pc, bank, mode);
+            }
             self.step_emulator(&mut texture, rect);
             let cpu_duration_ns = t_cycles * T_CYCLE_DURATION_NS;
             dur_ns += cpu_duration_ns;
         } 
+        self.finish_session();
+    }
+
+    /// Reports the profile and saves the code/data log (if enabled).
+    fn finish_session(&mut self) {
+        if let Some(profiler) = self.profiler.take() {
+            profiler.finish();
+        }
+        if let (Some(path), Some(log)) = (self.cdl_path.take(), self.cpu.code_data_log()) {
+            match log.save(&path) {
+                Ok(()) => println!("Saved code/data log to {}: {}", path.display(), log.summary()),
+                Err(e) => println!("Could not save code/data log to {}: {}", path.display(), e),
+            }
+        }
+        self.save_event_log();
+        if std::mem::take(&mut self.vram_dump_on_exit) {
+            self.dump_vram();
+        }
+    }
+
+    fn save_event_log(&mut self) {
+        let Some(log) = self.cpu.event_log() else { return };
+
+        if let Some(path) = self.events_path.take() {
+            match std::fs::write(&path, log.to_json()) {
+                Ok(()) => println!("Saved events of {} frames to {}", log.frames().count(), path),
+                Err(e) => println!("Could not save events to {}: {}", path, e),
+            }
+        }
+        if let Some(path) = self.events_overlay_path.take() {
+            let result = match log.render_overlay() {
+                Some(rgb) => save_png_rgb(&path, FRAME_DOTS, FRAME_LINES, &rgb).map_err(|e| e.to_string()),
+                None => Err(String::from("no frame was completed")),
+            };
+            match result {
+                Ok(()) => println!("Saved event overlay to {}", path),
+                Err(e) => println!("Could not save event overlay to {}: {}", path, e),
+            }
+        }
+    }
+
+    /// Prints where the CPU crashed (the instruction at pc in bank), after the panic message.
+    fn report_crash(&self, pc: u16, bank: usize) {
+        println!("Crashed at {}", self.describe_location(pc, bank));
+
+        let instruction = disassemble(|addr| self.cpu.peek_byte(addr), pc, bank, self.symbols.as_deref());
+        println!("    {}", instruction.display(bank));
+
+        let registers = self.cpu.registers();
+        println!("AF={:04X} BC={:04X} DE={:04X} HL={:04X} SP={:04X}",
+            registers.af, registers.bc, registers.de, registers.hl, registers.sp);
+    }
+
+    /// Reports the exceptions raised by the instruction at pc in bank.
+    fn report_exceptions(&mut self, pc: u16, bank: usize, mode: ExceptionMode) {
+        for exception in self.cpu.take_exceptions(pc) {
+            if matches!(mode, ExceptionMode::Log) && !self.logged_exceptions.insert((exception.kind, exception.addr, pc)) {
+                continue;
+            }
+
+            let instruction = disassemble(|addr| self.cpu.peek_byte(addr), pc, bank, self.symbols.as_deref());
+            println!("Exception: {} at {}: {}", exception.describe(), self.describe_location(pc, bank), instruction.text);
+
+            if matches!(mode, ExceptionMode::Break) {
+                if let Some(debugger) = &mut self.debugger {
+                    debugger.pause();
+                }
+                if let Some(gdb) = &mut self.gdb {
+                    gdb.pause();
+                }
+            }
+        }
+    }
+
+    /// Describes pc as a bank-qualified address, and its closest label (if any).
+    fn describe_location(&self, pc: u16, bank: usize) -> String {
+        let location = format_addr(pc, bank).trim().to_string();
+        match self.symbols.as_ref().and_then(|symbols| symbols.describe(pc, bank)) {
+            Some(label) => format!("{} ({})", location, label),
+            None => location,
+        }
     }
 
     /// Steps SDL2 joypad input, texture display and audio callback
@@ -153,19 +456,37 @@ impl Emulator {
 
                 self.canvas.copy(&texture, None, rect).unwrap();
                 self.canvas.present();
+
+                if let Some(profiler) = &mut self.profiler {
+                    profiler.end_frame();
+                }
             }
             None => {}
         };
     }
 
     fn get_events(&mut self) -> Result<(), &str> { 
+        let mut dump_vram = false;
+        let mut toggled_layer = None;
+        let mut next_palette = false;
         for event in self.event_pump.poll_iter() {
             match event {
                 Event::Quit {..} |
                 Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
                     self.cpu.save_mbc_state();
+                    self.finish_session();
                     return Err("User Exited");
                 },
+                Event::KeyDown { keycode: Some(DEBUGGER_KEY), .. } => {
+                    if let Some(debugger) = &mut self.debugger {
+                        debugger.pause();
+                    }
+                }
+                Event::KeyDown { keycode: Some(VRAM_DUMP_KEY), .. } => dump_vram = true,
+                Event::KeyDown { keycode: Some(PALETTE_KEY), .. } => next_palette = true,
+                Event::KeyDown { keycode: Some(key), .. } if LAYER_KEYS.iter().any(|(k, _)| *k == key) => {
+                    toggled_layer = LAYER_KEYS.iter().find(|(k, _)| *k == key).map(|(_, layer)| *layer);
+                }
                 Event::KeyDown { keycode: Some(key), ..} => {   
                     for i in 0..8 {
                         if KEYMAPPINGS[i] == key {
@@ -184,6 +505,18 @@ impl Emulator {
             }
         }
 
+        if dump_vram {
+            self.dump_vram();
+        }
+        if let Some(layer) = toggled_layer {
+            let ppu = self.cpu.bus_mut().ppu_mut();
+            let enabled = !ppu.layer_enabled(layer);
+            ppu.set_layer_enabled(layer, enabled);
+            println!("{:?} layer {}", layer, if enabled { "shown" } else { "hidden" });
+        }
+        if next_palette {
+            self.next_dmg_palette();
+        }
         Ok(())
     }
 }
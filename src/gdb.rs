@@ -0,0 +1,401 @@
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+use crate::cpu::{Access, Cpu, Registers, Watchpoints};
+
+// number of CPU steps between checks for an interrupt (Ctrl-C) from GDB while running
+const POLL_INTERVAL: u32 = 4096;
+
+const SIGINT: u8 = 2;
+const SIGTRAP: u8 = 5;
+
+// register numbers, in the order sent for 'g' (each is 16-bit little-endian)
+const REGISTER_COUNT: usize = 6;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum WatchKind {
+    Write,
+    Read,
+    Access,
+}
+
+struct Watch {
+    id: usize,
+    kind: WatchKind,
+    addr: u16,
+    len: u16,
+}
+
+enum Mode {
+    Stopped(String),
+    Continue,
+    Step,
+    Detached,
+}
+
+/// Stub for the GDB remote serial protocol, letting a GDB front-end control the CPU over TCP;
+/// registers are sent as AF, BC, DE, HL, SP, PC (16-bit little-endian).
+pub struct GdbStub {
+    stream: TcpStream,
+    breakpoints: Vec<u16>,
+    watches: Vec<Watch>,
+    next_id: usize,
+    mode: Mode,
+    skip_breakpoint: bool,
+    steps_since_poll: u32,
+    quit: bool,
+}
+
+impl GdbStub {
+    /// Waits for GDB to connect on the given local port; the CPU starts stopped.
+    pub fn listen(port: u16) -> io::Result<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", port))?;
+        println!("Waiting for GDB to connect on 127.0.0.1:{} (target remote :{})", port, port);
+        let (stream, addr) = listener.accept()?;
+        println!("GDB connected from {}", addr);
+        stream.set_nodelay(true)?;
+
+        Ok(GdbStub {
+            stream,
+            breakpoints: Vec::new(),
+            watches: Vec::new(),
+            next_id: 1,
+            mode: Mode::Stopped(format!("S{:02x}", SIGTRAP)),
+            skip_breakpoint: false,
+            steps_since_poll: 0,
+            quit: false,
+        })
+    }
+
+    /// Stops before the next instruction (unless detached).
+    pub fn pause(&mut self) {
+        if !matches!(self.mode, Mode::Detached) {
+            self.mode = Mode::Stopped(format!("S{:02x}", SIGTRAP));
+        }
+    }
+
+    /// Checks for breakpoints, watchpoints, stepping and interrupts before the CPU steps,
+    /// serving GDB's requests while stopped; returns false once GDB kills the target.
+    pub fn before_step(&mut self, cpu: &mut Cpu) -> bool {
+        if matches!(self.mode, Mode::Detached) {
+            return true;
+        }
+
+        if let Some(hit) = cpu.take_watch_hit() {
+            let watch = self.watches.iter().find(|watch| watch.id == hit.id);
+            let name = match (watch.map(|watch| watch.kind), hit.access) {
+                (Some(WatchKind::Access), _) => "awatch",
+                (_, Access::Read) => "rwatch",
+                (_, Access::Write) => "watch",
+            };
+            self.mode = Mode::Stopped(format!("T{:02x}{}:{:x};", SIGTRAP, name, hit.addr));
+        }
+        if cpu.take_debug_break() {
+            self.mode = Mode::Stopped(format!("S{:02x}", SIGTRAP));
+        }
+
+        let registers = cpu.registers();
+        match self.mode {
+            Mode::Step => self.mode = Mode::Stopped(format!("S{:02x}", SIGTRAP)),
+            Mode::Continue => {
+                let skip = std::mem::take(&mut self.skip_breakpoint);
+                if !skip && !registers.halted && self.breakpoints.contains(&registers.pc) {
+                    self.mode = Mode::Stopped(format!("S{:02x}", SIGTRAP));
+                } else if self.interrupted(cpu) {
+                    self.mode = Mode::Stopped(format!("S{:02x}", SIGINT));
+                }
+            }
+            _ => {}
+        }
+
+        if let Mode::Stopped(reply) = &self.mode {
+            let reply = reply.clone();
+            if let Err(e) = self.serve(cpu, &reply) {
+                println!("GDB disconnected: {}", e);
+                self.detach(cpu);
+            }
+        }
+
+        !self.quit
+    }
+
+    /// Returns true if GDB sent an interrupt (Ctrl-C), detaching if GDB disconnected;
+    /// only checks every POLL_INTERVAL steps. Other bytes are left for the next packet.
+    fn interrupted(&mut self, cpu: &mut Cpu) -> bool {
+        self.steps_since_poll += 1;
+        if self.steps_since_poll < POLL_INTERVAL {
+            return false;
+        }
+        self.steps_since_poll = 0;
+
+        let mut byte = [0];
+        if self.stream.set_nonblocking(true).is_err() {
+            return false;
+        }
+        let peeked = self.stream.peek(&mut byte);
+        let interrupted = matches!(peeked, Ok(1) if byte[0] == 0x03);
+        if interrupted {
+            let _ = self.stream.read(&mut byte);
+        }
+        let _ = self.stream.set_nonblocking(false);
+
+        if let Ok(0) = peeked {
+            println!("GDB disconnected");
+            self.detach(cpu);
+        }
+        interrupted
+    }
+
+    /// Sends the stop reply, then handles packets until GDB resumes, detaches or kills the target.
+    fn serve(&mut self, cpu: &mut Cpu, stop_reply: &str) -> io::Result<()> {
+        self.send(stop_reply)?;
+
+        while matches!(self.mode, Mode::Stopped(_)) && !self.quit {
+            let packet = self.receive()?;
+            let reply = self.handle(&packet, cpu);
+            if let Some(reply) = reply {
+                self.send(&reply)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Handles a packet, returning the reply (None if there is no reply, e.g. when resuming).
+    fn handle(&mut self, packet: &str, cpu: &mut Cpu) -> Option<String> {
+        let (command, args) = packet.split_at(packet.chars().next().map_or(0, char::len_utf8));
+        let reply = match command {
+            "?" => format!("S{:02x}", SIGTRAP),
+            "g" => {
+                let registers = cpu.registers();
+                (0..REGISTER_COUNT).map(|n| encode_u16(read_register(&registers, n))).collect()
+            }
+            "G" => {
+                let mut registers = cpu.registers();
+                for n in 0..REGISTER_COUNT {
+                    match args.get(n * 4..n * 4 + 4).and_then(decode_u16) {
+                        Some(value) => write_register(&mut registers, n, value),
+                        None => return Some(String::from("E01")),
+                    }
+                }
+                cpu.set_registers(registers);
+                String::from("OK")
+            }
+            "p" => match usize::from_str_radix(args, 16) {
+                Ok(n) if n < REGISTER_COUNT => encode_u16(read_register(&cpu.registers(), n)),
+                _ => String::from("E01"),
+            },
+            "P" => {
+                let (n, value) = args.split_once('=').unwrap_or(("", ""));
+                match (usize::from_str_radix(n, 16), decode_u16(value)) {
+                    (Ok(n), Some(value)) if n < REGISTER_COUNT => {
+                        let mut registers = cpu.registers();
+                        write_register(&mut registers, n, value);
+                        cpu.set_registers(registers);
+                        String::from("OK")
+                    }
+                    _ => String::from("E01"),
+                }
+            }
+            "m" => match parse_addr_len(args) {
+                Some((addr, len)) => (0..len)
+                    .map(|i| format!("{:02x}", cpu.peek_byte(addr.wrapping_add(i))))
+                    .collect(),
+                None => String::from("E01"),
+            },
+            "M" => {
+                let (range, data) = args.split_once(':').unwrap_or(("", ""));
+                match (parse_addr_len(range), decode_bytes(data)) {
+                    (Some((addr, len)), Some(bytes)) if bytes.len() == len as usize => {
+                        for (i, byte) in bytes.into_iter().enumerate() {
+                            cpu.bus_mut().write_byte(addr.wrapping_add(i as u16), byte);
+                        }
+                        // edits are not accesses by the CPU
+                        cpu.take_watch_hit();
+                        String::from("OK")
+                    }
+                    _ => String::from("E01"),
+                }
+            }
+            "Z" | "z" => self.handle_breakpoint(command == "Z", args, cpu),
+            "c" | "s" => {
+                if let Some(addr) = u16::from_str_radix(args, 16).ok().filter(|_| !args.is_empty()) {
+                    let mut registers = cpu.registers();
+                    registers.pc = addr;
+                    cpu.set_registers(registers);
+                }
+                if command == "c" {
+                    self.mode = Mode::Continue;
+                    self.skip_breakpoint = true;
+                } else {
+                    self.mode = Mode::Step;
+                }
+                return None;
+            }
+            "D" => {
+                self.detach(cpu);
+                String::from("OK")
+            }
+            "k" => {
+                self.quit = true;
+                return None;
+            }
+            "H" => String::from("OK"),
+            "q" if args.starts_with("Supported") => String::from("PacketSize=1000"),
+            "q" if args == "Attached" => String::from("1"),
+            "q" if args == "C" => String::from("QC1"),
+            "q" if args == "fThreadInfo" => String::from("m1"),
+            "q" if args == "sThreadInfo" => String::from("l"),
+            // unsupported packets get an empty reply
+            _ => String::new(),
+        };
+
+        Some(reply)
+    }
+
+    /// Handles Z/z (insert/remove) packets: type 0/1 breakpoints, 2 write, 3 read and 4 access watchpoints.
+    fn handle_breakpoint(&mut self, insert: bool, args: &str, cpu: &mut Cpu) -> String {
+        let (kind, range) = args.split_once(',').unwrap_or(("", ""));
+        let (addr, len) = match parse_addr_len(range) {
+            Some(range) => range,
+            None => return String::from("E01"),
+        };
+
+        let watch_kind = match kind {
+            "0" | "1" => {
+                if insert {
+                    self.breakpoints.push(addr);
+                } else if let Some(i) = self.breakpoints.iter().position(|bp| *bp == addr) {
+                    self.breakpoints.remove(i);
+                }
+                return String::from("OK");
+            }
+            "2" => WatchKind::Write,
+            "3" => WatchKind::Read,
+            "4" => WatchKind::Access,
+            _ => return String::new(),
+        };
+
+        if insert {
+            self.watches.push(Watch { id: self.next_id, kind: watch_kind, addr, len: len.max(1) });
+            self.next_id += 1;
+        } else {
+            self.watches.retain(|watch| !(watch.kind == watch_kind && watch.addr == addr));
+        }
+        self.sync_watchpoints(cpu);
+
+        String::from("OK")
+    }
+
+    /// Hands the watchpoints to the bus, which checks every access (if there are any).
+    fn sync_watchpoints(&self, cpu: &mut Cpu) {
+        let mut watchpoints = Watchpoints::new();
+        for watch in &self.watches {
+            let end = watch.addr.saturating_add(watch.len - 1);
+            let read = watch.kind != WatchKind::Write;
+            let write = watch.kind != WatchKind::Read;
+            watchpoints.add(watch.id, watch.addr, end, read, write);
+        }
+
+        cpu.set_watchpoints(if watchpoints.is_empty() { None } else { Some(watchpoints) });
+    }
+
+    /// Removes all breakpoints and watchpoints, and lets the emulator run freely.
+    fn detach(&mut self, cpu: &mut Cpu) {
+        self.breakpoints.clear();
+        self.watches.clear();
+        cpu.set_watchpoints(None);
+        self.mode = Mode::Detached;
+    }
+
+    /// Reads the next packet ($<data>#<checksum>), acknowledging it; acks and interrupts are skipped.
+    fn receive(&mut self) -> io::Result<String> {
+        loop {
+            let byte = self.read_byte()?;
+            if byte != b'$' {
+                continue;
+            }
+
+            let mut data = Vec::new();
+            loop {
+                match self.read_byte()? {
+                    b'#' => break,
+                    // escaped byte (XORed with 0x20)
+                    b'}' => data.push(self.read_byte()? ^ 0x20),
+                    byte => data.push(byte),
+                }
+            }
+            let checksum = [self.read_byte()?, self.read_byte()?];
+
+            let expected = u8::from_str_radix(&String::from_utf8_lossy(&checksum), 16).ok();
+            if expected == Some(checksum_of(&data)) {
+                self.stream.write_all(b"+")?;
+                return Ok(String::from_utf8_lossy(&data).to_string());
+            }
+            self.stream.write_all(b"-")?;
+        }
+    }
+
+    fn read_byte(&mut self) -> io::Result<u8> {
+        let mut byte = [0];
+        match self.stream.read(&mut byte)? {
+            0 => Err(io::Error::new(ErrorKind::UnexpectedEof, "connection closed")),
+            _ => Ok(byte[0]),
+        }
+    }
+
+    fn send(&mut self, data: &str) -> io::Result<()> {
+        let packet = format!("${}#{:02x}", data, checksum_of(data.as_bytes()));
+        self.stream.write_all(packet.as_bytes())?;
+        self.stream.flush()
+    }
+}
+
+fn checksum_of(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte))
+}
+
+fn read_register(registers: &Registers, n: usize) -> u16 {
+    match n {
+        0 => registers.af,
+        1 => registers.bc,
+        2 => registers.de,
+        3 => registers.hl,
+        4 => registers.sp,
+        _ => registers.pc,
+    }
+}
+
+fn write_register(registers: &mut Registers, n: usize, value: u16) {
+    match n {
+        0 => registers.af = value,
+        1 => registers.bc = value,
+        2 => registers.de = value,
+        3 => registers.hl = value,
+        4 => registers.sp = value,
+        _ => registers.pc = value,
+    }
+}
+
+fn encode_u16(value: u16) -> String {
+    value.to_le_bytes().iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn decode_u16(hex: &str) -> Option<u16> {
+    match decode_bytes(hex)?.as_slice() {
+        [lo, hi] => Some(u16::from_le_bytes([*lo, *hi])),
+        _ => None,
+    }
+}
+
+fn decode_bytes(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok()).collect()
+}
+
+/// Parses '<addr>,<len>' (both in hex).
+fn parse_addr_len(args: &str) -> Option<(u16, u16)> {
+    let (addr, len) = args.split_once(',')?;
+    Some((u16::from_str_radix(addr, 16).ok()?, u16::from_str_radix(len, 16).ok()?))
+}
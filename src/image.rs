@@ -0,0 +1,12 @@
+use std::fs::File;
+use std::io::{self, BufWriter};
+
+/// Saves RGB pixels (3 bytes each, row by row) as a PNG image of the given size.
+pub fn save_png_rgb(path: &str, width: usize, height: usize, rgb: &[u8]) -> io::Result<()> {
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(file, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()?.write_image_data(rgb)?;
+    Ok(())
+}
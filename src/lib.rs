@@ -8,13 +8,17 @@ mod apu;
 mod joypad;
 mod timer;
 mod cartridge;
+mod colourization;
+mod palette;
+pub mod disasm;
+pub mod symbols;
 
 pub use cartridge::Cartridge;
 use config::{AUDIO_SAMPLES, SAMPLING_RATE_HZ};
 use constants::{BYTES_PER_PIXEL, LCD_HEIGHT, LCD_WIDTH};
-pub use cpu::Cpu;
-
-use cpu::GBModel;
+pub use cpu::{Cpu, ExceptionMode, Fetch, GBModel, Interrupt, MemoryBus, Registers, Tracer, FRAME_DOTS, FRAME_LINES};
+pub use palette::{parse_shades, ColourCorrection, DmgPalette, PaletteRegister, Rgb555Palette};
+pub use ppu::Layer;
 use wasm_bindgen::prelude::*;
 
 #[wasm_bindgen]
@@ -48,6 +52,8 @@ pub mod constants {
 #[wasm_bindgen]
 pub struct Emulator {
     title: String,
+    // the title checksum and letter the CGB boot ROM colourizes the game by (see Cartridge::colourization_key)
+    colourization_key: Option<(u8, u8)>,
     cpu: Cpu,
     audio_output_flat: [f32; 2 * AUDIO_SAMPLES],
 }
@@ -57,6 +63,7 @@ impl Emulator {
     pub fn new(cartridge_bytes: &[u8]) -> Self {
         let cartridge = Cartridge::from_bytes(cartridge_bytes);
         let title = cartridge.get_title();
+        let colourization_key = cartridge.colourization_key();
 
         let model = if cartridge.cgb_compatible() {
             GBModel::CGB
@@ -67,6 +74,7 @@ impl Emulator {
 
         Emulator { 
             title,
+            colourization_key,
             cpu: Cpu::new(cartridge, model),
             audio_output_flat: [0.0; 2 * AUDIO_SAMPLES],
         }
@@ -128,6 +136,87 @@ impl Emulator {
         self.cpu.update_joypad(status)
     }
 
+    /// Draws every sprite on a line, not just the first 10, without affecting emulated timing.
+    pub fn set_unlimited_sprites(&mut self, enabled: bool) {
+        self.cpu.bus_mut().ppu_mut().set_unlimited_objects(enabled)
+    }
+
+    /// Selects a built-in DMG palette (green, pocket, light or contrast); returns false if there is none by that name.
+    pub fn set_dmg_palette(&mut self, name: &str) -> bool {
+        match DmgPalette::preset(name) {
+            Some(palette) => {
+                self.cpu.bus_mut().ppu_mut().set_dmg_palette(palette);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Sets the display colours of a DMG palette register (bgp, obp0 or obp1) to 4 RGB colours in hex,
+    /// lightest first (e.g. "E0F8D0,88C070,346856,081820"); returns false if either is invalid.
+    #[cfg(target_arch = "wasm32")]
+    pub fn set_dmg_palette_colours(&mut self, register: &str, colours: &str) -> bool {
+        match (PaletteRegister::parse(register), parse_shades(colours)) {
+            (Some(register), Ok(shades)) => {
+                let ppu = self.cpu.bus_mut().ppu_mut();
+                let mut palette = ppu.dmg_palette();
+                palette.set_shades(register, shades);
+                ppu.set_dmg_palette(palette);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Sets how CGB colours are displayed: mode is none, cgb, modern or gba, and brightness (-1.0 to 1.0)
+    /// darkens or lightens (like a frontlight) the corrected colours; returns false for unknown modes.
+    pub fn set_colour_correction(&mut self, mode: &str, brightness: f32) -> bool {
+        match ColourCorrection::parse(mode) {
+            Some(correction) => {
+                self.cpu.bus_mut().ppu_mut().set_colour_correction(correction, brightness);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Colours a DMG game with the palette the CGB boot ROM gives it (by its title).
+    pub fn colourize(&mut self) {
+        let palette = colourization::palette_for_game(self.colourization_key);
+        self.cpu.bus_mut().ppu_mut().set_rgb555_dmg_palette(palette)
+    }
+
+    /// Colours a DMG game with the palette of a CGB boot ROM button combination (e.g. "left+a");
+    /// returns false if there is none.
+    pub fn colourize_with_buttons(&mut self, buttons: &str) -> bool {
+        match colourization::palette_for_buttons(buttons) {
+            Some(palette) => {
+                self.cpu.bus_mut().ppu_mut().set_rgb555_dmg_palette(palette);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Shows or hides the BG, window or object layer in the renderer (for debugging).
+    pub fn set_bg_enabled(&mut self, enabled: bool) {
+        self.cpu.bus_mut().ppu_mut().set_layer_enabled(Layer::Background, enabled)
+    }
+
+    pub fn set_window_enabled(&mut self, enabled: bool) {
+        self.cpu.bus_mut().ppu_mut().set_layer_enabled(Layer::Window, enabled)
+    }
+
+    pub fn set_objects_enabled(&mut self, enabled: bool) {
+        self.cpu.bus_mut().ppu_mut().set_layer_enabled(Layer::Objects, enabled)
+    }
+
+    /// Shows or hides the object of an OAM entry (0-39) in the renderer (for debugging);
+    /// returns false if there is no such entry.
+    pub fn set_object_enabled(&mut self, index: usize, enabled: bool) -> bool {
+        self.cpu.bus_mut().ppu_mut().set_object_enabled(index, enabled)
+    }
+
     pub fn save_game(&mut self) {
         self.cpu.save_mbc_state()
     }
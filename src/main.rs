@@ -11,23 +11,125 @@ mod apu;
 mod joypad;
 mod timer;
 mod cartridge;
+mod colourization;
+mod palette;
+mod disasm;
 mod emulator;
+mod cli;
+mod debugger;
+mod gdb;
+mod image;
+mod profiler;
+mod symbols;
+mod vram;
 
 use cartridge::Cartridge;
+use cli::Command;
+use cpu::{CodeDataLog, Tracer};
+use debugger::Debugger;
+use gdb::GdbStub;
 use emulator::Emulator;
+use profiler::Profiler;
+use symbols::Symbols;
 use gbemulib::constants;
 
 #[cfg(target_arch = "wasm32")]
 use gbemulib::{load_from_db, save_to_db, log};
 
-const ROM_PATH: &str = "roms/pokemoncrystal.gbc";
-const WITH_BOOTROM: bool = false;
-
 #[cfg(not(target_arch = "wasm32"))]
 fn main() -> Result<(), String> {
-    let cartridge = Cartridge::from_file(ROM_PATH, WITH_BOOTROM);
+    let options = match Command::parse(std::env::args().skip(1)) {
+        Ok(Command::Run(options)) => *options,
+        Ok(Command::Disasm { rom_path, bank, from, count }) => {
+            let rom = std::fs::read(&rom_path).map_err(|e| format!("{}: {}", rom_path, e))?;
+            let symbols = load_symbols(&rom_path)?;
+            for line in disasm::disassemble_rom(&rom, bank, from, count, symbols.as_deref()) {
+                println!("{}", line);
+            }
+            return Ok(());
+        }
+        Err(message) => {
+            eprintln!("{}", message);
+            std::process::exit(2);
+        }
+    };
+
+    let symbols = load_symbols(&options.rom_path)?;
+    let cartridge = Cartridge::from_file(&options.rom_path, options.with_bootrom);
+    let rom_size = cartridge.rom_size();
+    let colourization_key = cartridge.colourization_key();
     let mut emulator = Emulator::load_cartridge(cartridge)?;
+    emulator.set_ly_stub(options.ly_stub);
+    emulator.set_unlimited_sprites(options.unlimited_sprites);
+    if let Some((name, palette)) = options.dmg_palette() {
+        emulator.set_dmg_palette(&name, palette);
+    }
+    if options.colour_correction.is_some() || options.brightness != 0.0 {
+        emulator.set_colour_correction(options.colour_correction.unwrap_or_default(), options.brightness);
+    }
+    match options.colourize.as_deref() {
+        Some("auto") => emulator.set_rgb555_dmg_palette("colourized", colourization::palette_for_game(colourization_key)),
+        Some(buttons) => emulator.set_rgb555_dmg_palette(buttons, colourization::palette_for_buttons(buttons).unwrap()),
+        None => {}
+    }
+    emulator.set_debug_hooks(options.debug_hooks);
+    if let Some(mode) = options.exceptions {
+        emulator.set_exception_mode(mode);
+    }
+    if let Some(symbols) = &symbols {
+        emulator.set_symbols(symbols.clone());
+    }
+
+    if let Some(path) = &options.trace {
+        let mut tracer = Tracer::log_to(path).map_err(|e| format!("{}: {}", path, e))?;
+        tracer.set_annotated(options.annotate_trace);
+        if let Some(symbols) = &symbols {
+            tracer.set_symbols(symbols.clone());
+        }
+        emulator.set_tracer(tracer);
+    } else if let Some(path) = &options.compare {
+        let tracer = Tracer::compare_with(path).map_err(|e| format!("{}: {}", path, e))?;
+        emulator.set_tracer(tracer);
+    }
+
+    if options.cdl {
+        let path = CodeDataLog::path_for_rom(&options.rom_path);
+        let log = CodeDataLog::load_or_new(&path, rom_size).map_err(|e| format!("{}: {}", path.display(), e))?;
+        emulator.set_code_data_log(log, path);
+    }
+    if let Some(dir) = &options.vram_dump {
+        emulator.set_vram_dump_dir(dir);
+    }
+    if options.events.is_some() || options.events_overlay.is_some() {
+        emulator.set_event_log(options.events.clone(), options.events_overlay.clone());
+    }
+    if let Some(path) = &options.profile {
+        emulator.set_profiler(Profiler::new(path, symbols.clone()));
+    }
+
+    if options.debug {
+        println!("Debugger attached (type help for commands)");
+        emulator.set_debugger(Debugger::new(symbols));
+    } else if let Some(port) = options.gdb_port {
+        let gdb = GdbStub::listen(port).map_err(|e| format!("GDB stub: {}", e))?;
+        emulator.set_gdb(gdb);
+    }
+
     emulator.run_for_duration(40e12 as u64);
 
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Loads the RGBDS symbol file next to the ROM (e.g. game.sym for game.gb), if there is one.
+#[cfg(not(target_arch = "wasm32"))]
+fn load_symbols(rom_path: &str) -> Result<Option<std::rc::Rc<Symbols>>, String> {
+    match Symbols::for_rom(rom_path) {
+        Some(Ok(symbols)) if symbols.is_empty() => Ok(None),
+        Some(Ok(symbols)) => {
+            eprintln!("Loaded {} symbols", symbols.len());
+            Ok(Some(std::rc::Rc::new(symbols)))
+        }
+        Some(Err(e)) => Err(format!("symbol file: {}", e)),
+        None => Ok(None),
+    }
+}
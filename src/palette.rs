@@ -0,0 +1,259 @@
+use std::cmp::min;
+
+use crate::config::{COLOURS, COLOUR_CORRECTION, DMG_PALETTE};
+use crate::constants::BYTES_PER_PIXEL;
+
+/// Display colours (BGRA, as in the frame buffer) of the 4 shades of a DMG palette register, lightest first.
+pub type Shades = [[u8; BYTES_PER_PIXEL]; 4];
+
+/// Built-in palettes, by name.
+pub const PRESETS: [(&str, Shades); 4] = [
+    ("green", COLOURS),
+    ("pocket", [rgb(0xC4, 0xCF, 0xA1), rgb(0x8B, 0x95, 0x6D), rgb(0x4D, 0x53, 0x3C), rgb(0x1F, 0x1F, 0x1F)]),
+    ("light", [rgb(0x8C, 0xF0, 0xDC), rgb(0x4C, 0xC0, 0xA8), rgb(0x28, 0x80, 0x70), rgb(0x10, 0x40, 0x38)]),
+    ("contrast", [rgb(0xFF, 0xFF, 0xFF), rgb(0xAA, 0xAA, 0xAA), rgb(0x55, 0x55, 0x55), rgb(0x00, 0x00, 0x00)]),
+];
+
+const fn rgb(red: u8, green: u8, blue: u8) -> [u8; BYTES_PER_PIXEL] {
+    [blue, green, red, 0xFF]
+}
+
+/// DMG palette register a pixel's colour comes from.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PaletteRegister {
+    Bgp,
+    Obp0,
+    Obp1,
+}
+
+impl PaletteRegister {
+    /// Parses a register by name (bgp, obp0 or obp1).
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "bgp" | "bg" => Some(PaletteRegister::Bgp),
+            "obp0" => Some(PaletteRegister::Obp0),
+            "obp1" => Some(PaletteRegister::Obp1),
+            _ => None,
+        }
+    }
+}
+
+/// Display colours of DMG games: separate shades for BGP (BG and window), OBP0 and OBP1.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct DmgPalette {
+    pub bgp: Shades,
+    pub obp0: Shades,
+    pub obp1: Shades,
+}
+
+impl DmgPalette {
+    /// Uses the same shades for all palette registers.
+    pub const fn uniform(shades: Shades) -> Self {
+        DmgPalette { bgp: shades, obp0: shades, obp1: shades }
+    }
+
+    /// Returns the built-in palette with the given name (see PRESETS).
+    pub fn preset(name: &str) -> Option<Self> {
+        PRESETS.iter()
+            .find(|(preset, _)| preset.eq_ignore_ascii_case(name))
+            .map(|(_, shades)| DmgPalette::uniform(*shades))
+    }
+
+    pub fn shades(&self, register: PaletteRegister) -> &Shades {
+        match register {
+            PaletteRegister::Bgp => &self.bgp,
+            PaletteRegister::Obp0 => &self.obp0,
+            PaletteRegister::Obp1 => &self.obp1,
+        }
+    }
+
+    pub fn set_shades(&mut self, register: PaletteRegister, shades: Shades) {
+        match register {
+            PaletteRegister::Bgp => self.bgp = shades,
+            PaletteRegister::Obp0 => self.obp0 = shades,
+            PaletteRegister::Obp1 => self.obp1 = shades,
+        }
+    }
+}
+
+impl Default for DmgPalette {
+    /// The palette named by DMG_PALETTE (in config.rs), or COLOURS if there is none.
+    fn default() -> Self {
+        DmgPalette::preset(DMG_PALETTE).unwrap_or(DmgPalette::uniform(COLOURS))
+    }
+}
+
+/// A DMG palette in CGB colours (RGB555, 4 per palette register, lightest first), such as the
+/// CGB boot ROM colourizes DMG games with; displayed with the CGB colour correction and brightness.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Rgb555Palette {
+    pub bgp: [u16; 4],
+    pub obp0: [u16; 4],
+    pub obp1: [u16; 4],
+}
+
+impl Rgb555Palette {
+    /// Returns the display colours, converted by lut.
+    pub fn display_palette(&self, lut: &ColourLut) -> DmgPalette {
+        let shades = |colours: [u16; 4]| colours.map(|colour| lut.get(colour));
+        DmgPalette { bgp: shades(self.bgp), obp0: shades(self.obp0), obp1: shades(self.obp1) }
+    }
+}
+
+/// Parses shades from 4 RGB colours in hex, lightest first (e.g. "E0F8D0,88C070,346856,081820").
+pub fn parse_shades(colours: &str) -> Result<Shades, String> {
+    let parsed: Vec<[u8; BYTES_PER_PIXEL]> = colours.split(',')
+        .map(|colour| {
+            let hex = colour.trim().trim_start_matches('#');
+            match u32::from_str_radix(hex, 16) {
+                Ok(value) if hex.len() == 6 => Ok(rgb((value >> 16) as u8, (value >> 8) as u8, value as u8)),
+                _ => Err(format!("invalid colour {} (expected RRGGBB in hex)", colour)),
+            }
+        })
+        .collect::<Result<_, _>>()?;
+
+    parsed.try_into().map_err(|_| format!("expected 4 colours, lightest first, in {}", colours))
+}
+
+/// How CGB colours (RGB555) are converted to display colours.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ColourCorrection {
+    /// Scales each channel to 8 bits
+    None,
+    /// Mixes channels and darkens, to look like a CGB screen
+    Cgb,
+    /// Mixes channels like Cgb, but in linear light (with gamma correction), so mid-tones stay brighter
+    Modern,
+    /// Looks like a GBA screen (which CGB games can also be played on)
+    Gba,
+}
+
+impl ColourCorrection {
+    pub const NAMES: [&'static str; 4] = ["none", "cgb", "modern", "gba"];
+
+    /// Parses a mode by name (see NAMES).
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "none" => Some(ColourCorrection::None),
+            "cgb" => Some(ColourCorrection::Cgb),
+            "modern" => Some(ColourCorrection::Modern),
+            "gba" => Some(ColourCorrection::Gba),
+            _ => None,
+        }
+    }
+
+    /// Converts an RGB555 colour to a display colour (BGRA); brightness (-1.0 to 1.0) darkens
+    /// towards black, or lightens towards white like a frontlight, after correction.
+    pub fn apply(self, colour: u16, brightness: f32) -> [u8; BYTES_PER_PIXEL] {
+        let r5 = (colour & 0x1F) as u32;
+        let g5 = ((colour >> 5) & 0x1F) as u32;
+        let b5 = ((colour >> 10) & 0x1F) as u32;
+
+        let [red, green, blue] = match self {
+            ColourCorrection::None => [r5, g5, b5].map(|c| ((c * 255 + 15) / 31) as f32 / 255.0),
+            ColourCorrection::Cgb => {
+                // CREDITS FOR COLOUR CORRECTION ALGORITHM:
+                // https://saveweb.github.io/near.sh/articles/video/color-emulation.html
+                let r = r5 * 26 + g5 * 4  + b5 * 2;
+                let g =           g5 * 24 + b5 * 8;
+                let b = r5 * 6  + g5 * 4  + b5 * 22;
+                [r, g, b].map(|c| (min(960, c) >> 2) as f32 / 255.0)
+            }
+            ColourCorrection::Modern => {
+                let [r, g, b] = [r5, g5, b5].map(|c| (c as f32 / 31.0).powf(2.2));
+                let mix = |weights: [f32; 3]| ((weights[0] * r + weights[1] * g + weights[2] * b) / 32.0).powf(1.0 / 2.2);
+                [mix([26.0, 4.0, 2.0]), mix([0.0, 24.0, 8.0]), mix([6.0, 4.0, 22.0])]
+            }
+            ColourCorrection::Gba => {
+                // from the same article: the GBA's screen has a gamma of about 4.0
+                let [r, g, b] = [r5, g5, b5].map(|c| (c as f32 / 31.0).powf(4.0));
+                let mix = |weights: [f32; 3]| ((weights[0] * r + weights[1] * g + weights[2] * b) / 255.0).powf(1.0 / 2.2) * 255.0 / 280.0;
+                [mix([255.0, 50.0, 0.0]), mix([10.0, 230.0, 30.0]), mix([50.0, 10.0, 220.0])]
+            }
+        };
+
+        let [red, green, blue] = [red, green, blue].map(|c| {
+            let c = if brightness >= 0.0 { c + (1.0 - c) * brightness } else { c * (1.0 + brightness) };
+            (c.clamp(0.0, 1.0) * 255.0).round() as u8
+        });
+        [blue, green, red, 0xFF]
+    }
+}
+
+impl Default for ColourCorrection {
+    /// The mode named by COLOUR_CORRECTION (in config.rs), or Cgb if there is none.
+    fn default() -> Self {
+        ColourCorrection::parse(COLOUR_CORRECTION).unwrap_or(ColourCorrection::Cgb)
+    }
+}
+
+/// Display colours of all 32768 RGB555 colours for a colour correction and brightness,
+/// so that they are not computed per pixel.
+pub struct ColourLut {
+    colours: Vec<[u8; BYTES_PER_PIXEL]>,
+}
+
+impl ColourLut {
+    pub fn new(correction: ColourCorrection, brightness: f32) -> Self {
+        let brightness = brightness.clamp(-1.0, 1.0);
+        let colours = (0..0x8000).map(|colour| correction.apply(colour, brightness)).collect();
+        ColourLut { colours }
+    }
+
+    /// Returns the display colour (BGRA) of an RGB555 colour (bit 15 is ignored).
+    pub fn get(&self, colour: u16) -> [u8; BYTES_PER_PIXEL] {
+        self.colours[(colour & 0x7FFF) as usize]
+    }
+}
+
+impl Default for ColourLut {
+    fn default() -> Self {
+        ColourLut::new(ColourCorrection::default(), 0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_shades, ColourCorrection, ColourLut, DmgPalette, PaletteRegister, PRESETS};
+    use crate::config::COLOURS;
+
+    #[test]
+    fn palette_test() {
+        assert_eq!(DmgPalette::preset("green"), Some(DmgPalette::uniform(COLOURS)));
+        assert_eq!(DmgPalette::preset("Pocket"), Some(DmgPalette::uniform(PRESETS[1].1)));
+        assert_eq!(DmgPalette::preset("sepia"), None);
+
+        let shades = parse_shades("FFFFFF, #aa5500,555555,000000").unwrap();
+        assert_eq!(shades[0], [0xFF, 0xFF, 0xFF, 0xFF]);
+        assert_eq!(shades[1], [0x00, 0x55, 0xAA, 0xFF]);
+        assert!(parse_shades("FFFFFF,AAAAAA,555555").is_err());
+        assert!(parse_shades("FFFFFF,AAAAAA,555555,00000G").is_err());
+
+        let mut palette = DmgPalette::uniform(COLOURS);
+        palette.set_shades(PaletteRegister::parse("obp1").unwrap(), shades);
+        assert_eq!(*palette.shades(PaletteRegister::Obp1), shades);
+        assert_eq!(*palette.shades(PaletteRegister::Obp0), COLOURS);
+    }
+
+    #[test]
+    fn colour_correction_test() {
+        let white = 0x7FFF;
+        assert_eq!(ColourCorrection::None.apply(white, 0.0), [0xFF, 0xFF, 0xFF, 0xFF]);
+        assert_eq!(ColourCorrection::Cgb.apply(white, 0.0), [240, 240, 240, 0xFF]);
+        assert_eq!(ColourCorrection::None.apply(0x001F, 0.0), [0x00, 0x00, 0xFF, 0xFF]);
+        for name in ColourCorrection::NAMES {
+            let correction = ColourCorrection::parse(name).unwrap();
+            assert_eq!(correction.apply(0x0000, 0.0)[..3], [0, 0, 0]);
+            assert_eq!(correction.apply(0x1234, 1.0), [0xFF, 0xFF, 0xFF, 0xFF]);
+            assert_eq!(correction.apply(0x1234, -1.0), [0x00, 0x00, 0x00, 0xFF]);
+        }
+
+        // the Modern mode is brighter in mid-tones than the Cgb mode
+        let grey = 0x10 | (0x10 << 5) | (0x10 << 10);
+        assert!(ColourCorrection::Modern.apply(grey, 0.0)[1] > ColourCorrection::Cgb.apply(grey, 0.0)[1]);
+
+        let lut = ColourLut::new(ColourCorrection::Gba, 0.25);
+        assert_eq!(lut.get(0x1234), ColourCorrection::Gba.apply(0x1234, 0.25));
+        assert_eq!(lut.get(0x8000 | 0x1234), lut.get(0x1234));
+    }
+}
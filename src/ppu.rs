@@ -2,13 +2,16 @@ use std::cmp::min;
 
 use crate::cpu::GBModel;
 use crate::constants::{BYTES_PER_PIXEL, LCD_BYTE_WIDTH};
-use crate::config::{WITH_COLOUR_CORRECTION, COLOURS};
+use crate::palette::{ColourCorrection, ColourLut, DmgPalette, PaletteRegister, Rgb555Palette};
 
 const TILE_SIZE: usize = 16;
 const TILE_ENTRIES: usize = 384;
 
 const OAM_ENTRY_SIZE: usize = 4;
 const OAM_ENTRIES: usize = 40;
+// objects selected per line by the OAM scan
+const LINE_OBJECTS: usize = 10;
+const OAM_ROWS: usize = 20;
 
 const TILE_MAP_SIZE: usize = 0x0400;
 
@@ -23,6 +26,28 @@ const MODE_1_DOTS: u32 = SCAN_LINE_DOTS * 10;
 const MODE_2_DOTS: u32 = 80;
 const MODE_3_MIN_DOTS: u32 = 172;
 
+// the first line after the LCD is turned on starts with mode 0 instead of mode 2,
+// and is 4 dots shorter than a normal line
+const LCD_ON_MODE_0_DOTS: u32 = MODE_2_DOTS - 4;
+
+// LY=LYC comparisons are blocked for the first few dots of each line
+const LYC_BLOCKED_DOTS: u32 = 4;
+
+/// Types of CPU accesses that trigger the OAM corruption bug (DMG only).
+pub enum OamCorruption {
+    Write,
+    Read,
+    ReadIncrease,
+}
+
+/// Layers drawn by the renderer, which can be hidden for debugging (see Ppu::set_layer_enabled).
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Layer {
+    Background,
+    Window,
+    Objects,
+}
+
 #[derive(PartialEq)]
 enum Mode {
     HBlank0, 
@@ -34,7 +59,10 @@ enum Mode {
 pub struct Ppu {
     model: GBModel,
     frame_buffer: [u8; LCD_BYTE_WIDTH * LCD_HEIGHT],
+    #[cfg(test)]
+    index_buffer: [u16; LCD_WIDTH * LCD_HEIGHT],
     stat_triggered: bool,
+    vblank_triggered: bool,
     entered_vblank: bool,
     tile_data0: [[u8; TILE_SIZE]; TILE_ENTRIES],
     tile_map0: [u8; TILE_MAP_SIZE],
@@ -54,6 +82,9 @@ pub struct Ppu {
     wx: u8,
 
     stat_line: bool,
+    stat_write_irq: bool,
+    lcd_on_line: bool,
+    skip_frame: bool,
     mode: Mode,
     mode_elapsed_dots: u32,
     mode_3_dots: u32,
@@ -64,7 +95,6 @@ pub struct Ppu {
     win_counter: usize,
     obj_buffer_index: usize,
     obj_buffer: Vec<OAMEntry>,
-    last_vblank_scanline: u32,
 
     // CGB_ONLY
     vbk: u8,
@@ -79,6 +109,19 @@ pub struct Ppu {
 
     // for HBlank DMA transfer (CGB only)
     entered_hblank: bool,
+
+    // for debugging, only affects rendering: hidden layers (by Layer), and hidden OAM entries (by bit)
+    hidden_layers: [bool; 3],
+    hidden_objects: u64,
+
+    // display colours of DMG colour indices, by palette register, and of CGB (RGB555) colours
+    dmg_palette: DmgPalette,
+    colour_lut: ColourLut,
+    // CGB colours dmg_palette was converted from (e.g. by colourization), if any
+    rgb555_palette: Option<Rgb555Palette>,
+
+    // enhancement: draws every object on a line, past the first LINE_OBJECTS (display only)
+    unlimited_objects: bool,
 }
 
 impl Ppu {
@@ -102,9 +145,15 @@ impl Ppu {
             wy: 0,
             wx: 0,
             frame_buffer: [0; LCD_BYTE_WIDTH * LCD_HEIGHT],
+            #[cfg(test)]
+            index_buffer: [0; LCD_WIDTH * LCD_HEIGHT],
             stat_triggered: false,
+            vblank_triggered: false,
             entered_vblank: false,
             stat_line: false,
+            stat_write_irq: false,
+            lcd_on_line: false,
+            skip_frame: false,
             mode: Mode::VBlank1,
             mode_elapsed_dots: 0,
             mode_3_dots: 0,
@@ -115,7 +164,6 @@ impl Ppu {
             win_counter: 0,
             obj_buffer_index: 0,
             obj_buffer: Vec::new(),
-            last_vblank_scanline: 0,
 
             vbk: 0,
             bgpi: 0,
@@ -127,18 +175,26 @@ impl Ppu {
             cram_bg: [0; CRAM_SIZE],
             cram_obj: [0; CRAM_SIZE],
             entered_hblank: false,
+            hidden_layers: [false; 3],
+            hidden_objects: 0,
+            dmg_palette: DmgPalette::default(),
+            colour_lut: ColourLut::default(),
+            rgb555_palette: None,
+            unlimited_objects: false,
         }
     }
 
     /// Steps through the PPU over the given period (in dots).
     /// NOTE: 1 dot = 1 T-Cycle (= 1/4 M-Cycle)
     pub fn step(&mut self, dots: u32) {
-        if self.lcd_ppu_disabled() { return; }
-        self.stat_triggered = false;
+        self.stat_triggered = std::mem::take(&mut self.stat_write_irq);
+        self.vblank_triggered = false;
         self.entered_hblank = false;
+        if self.lcd_ppu_disabled() { return; }
 
         let next_dots = self.mode_elapsed_dots + dots;
         let mode_end = match self.mode {
+            Mode::HBlank0 if self.lcd_on_line => LCD_ON_MODE_0_DOTS,
             Mode::HBlank0 => SCAN_LINE_DOTS - self.mode_3_dots - MODE_2_DOTS,
             Mode::VBlank1 => MODE_1_DOTS,
             Mode::OamScan2 => MODE_2_DOTS,
@@ -147,21 +203,31 @@ impl Ppu {
 
         if next_dots < mode_end {
             self.step_mode(dots);
-            self.mode_elapsed_dots += dots;    
+            self.mode_elapsed_dots = next_dots;
         } else {
             // new mode will be reached immediately after / during current cycle(s)
             self.step_mode(mode_end - self.mode_elapsed_dots);
+            self.mode_elapsed_dots = mode_end;
+            self.update_stat();
             self.next_mode();
 
             // runs if new mode is reached partway through current cycle(s)
+            self.mode_elapsed_dots = 0;
             self.step_mode(next_dots - mode_end);
             self.mode_elapsed_dots = next_dots - mode_end;
         }
+
+        self.update_stat();
     }
 
     // Updates PPU to next mode state.
     fn next_mode(&mut self) {
         self.mode = match self.mode {
+            Mode::HBlank0 if self.lcd_on_line => {
+                // there is no OAM scan on the first line after the LCD is turned on
+                self.lcd_on_line = false;
+                self.start_drawing()
+            },
             Mode::HBlank0 => {
                 self.cur_pixel_x = 0;
                 self.obj_buffer = Vec::new();
@@ -172,7 +238,13 @@ impl Ppu {
                     self.wy_cond = false;
                     self.win_counter = 0;
                     self.entered_vblank = true;
-                    self.last_vblank_scanline = 0;
+                    self.vblank_triggered = true;
+
+                    // the first frame after the LCD is turned on is not displayed
+                    if self.skip_frame {
+                        self.skip_frame = false;
+                        self.clear_frame();
+                    }
                     Mode::VBlank1
                 } else {        
                     self.win_counter += self.line_has_window as usize;
@@ -184,17 +256,7 @@ impl Ppu {
                 self.ly = 0;
                 Mode::OamScan2
             },
-            Mode::OamScan2 => {
-                self.wy_cond |= self.wy == self.ly;
-                self.wx_cond = false;
-                self.obj_buffer_index = 0;
-
-                if !self.is_cgb() || (self.opri & 0x01) != 0 {
-                    self.obj_buffer.sort_by(|a, b| { a.x.cmp(&b.x)});
-                }
-                self.mode_3_dots = self.calc_mode_3_dots();
-                Mode::Drawing3
-            },
+            Mode::OamScan2 => self.start_drawing(),
             Mode::Drawing3 => {
                 self.entered_hblank = true;
                 Mode::HBlank0
@@ -202,29 +264,42 @@ impl Ppu {
         };
     }
 
+    /// Sets up the current line for drawing; returns the drawing mode.
+    fn start_drawing(&mut self) -> Mode {
+        self.wy_cond |= self.wy == self.ly;
+        self.wx_cond = false;
+        self.obj_buffer_index = 0;
+
+        // timed by the objects the scan selects (the first LINE_OBJECTS, in OAM order), before sorting
+        self.mode_3_dots = self.calc_mode_3_dots();
+        if !self.is_cgb() || (self.opri & 0x01) != 0 {
+            self.obj_buffer.sort_by(|a, b| { a.x.cmp(&b.x)});
+        }
+        Mode::Drawing3
+    }
+
     /// ASSUME: self.mode_elapsed_dots + dots will NOT exceed duration of current mode.
-    /// Step through period (in dots) over the current mode (do nothing for mode 1 and 0).
+    /// Step through period (in dots) over the current mode (do nothing for mode 0).
     fn step_mode(&mut self, dots: u32) {
-        if dots == 0 { return; }
-
         match self.mode {
             Mode::VBlank1 => {
-                if self.last_vblank_scanline + dots >= SCAN_LINE_DOTS {
-                    self.ly += 1;
-                }
-                self.last_vblank_scanline = (self.last_vblank_scanline + dots) % SCAN_LINE_DOTS;
-
-                if self.ly == 153 && self.last_vblank_scanline >= 4 {
-                    self.ly = 0;
-                }
+                let elapsed = self.mode_elapsed_dots + dots;
+                let line = elapsed / SCAN_LINE_DOTS;
+
+                // LY wraps to 0 early, 4 dots into line 153, and stays there for the rest of VBlank
+                self.ly = match line {
+                    0..=8 => LCD_HEIGHT as u8 + line as u8,
+                    9 if elapsed % SCAN_LINE_DOTS < 4 => 153,
+                    _ => 0,
+                };
             }
             Mode::OamScan2 => {
                 let mut fetches = (dots + 1) / 2;
-                while fetches > 0 && self.obj_buffer_index < OAM_ENTRIES && self.obj_buffer.len() < 10 {
+                while fetches > 0 && self.obj_buffer_index < OAM_ENTRIES && self.obj_buffer.len() < self.line_object_limit() {
 
                     let obj_y = self.oam[self.obj_buffer_index][0];   
                     if self.ly + 16 >= obj_y && self.ly + 16 < obj_y + self.obj_size()  {
-                        self.obj_buffer.push(OAMEntry::new(self.oam[self.obj_buffer_index]));
+                        self.obj_buffer.push(OAMEntry::new(self.obj_buffer_index, self.oam[self.obj_buffer_index]));
                     }
 
                     self.obj_buffer_index += 1;
@@ -237,10 +312,14 @@ impl Ppu {
                     self.wx_cond = self.wx as usize <= self.cur_pixel_x + 7;
 
                     // future TODO (maybe): implement BG and OAM FIFO 
-                    let colour = self.render_pixel(self.cur_pixel_x, self.ly as usize); 
+                    let (colour, register) = self.render_pixel(self.cur_pixel_x, self.ly as usize); 
+                    #[cfg(test)]
+                    {
+                        self.index_buffer[usize::from(self.ly) * LCD_WIDTH + self.cur_pixel_x] = colour;
+                    }
                     let display_colour = match self.model {
-                        GBModel::DMG => COLOURS[colour as usize],
-                        GBModel::CGB => Ppu::rgb555_to_argb8888(colour),
+                        GBModel::DMG => self.dmg_palette.shades(register)[colour as usize],
+                        GBModel::CGB => self.colour_lut.get(colour),
                     };
 
                     for i in 0..BYTES_PER_PIXEL {
@@ -255,15 +334,13 @@ impl Ppu {
             }
             _ => {}
         }
-
-        self.update_stat();
     }
 
-    /// Returns colour index (for DMG), or RGB555 representation (for CGB) of pixel at position (lcd_x, lcd_y) 
-    fn render_pixel(&mut self, lcd_x: usize, lcd_y: usize) -> u16 {
-        let (mut colour, bg_priority, bg_is_0) = self.apply_bg(lcd_x, lcd_y);
-        colour = self.render_obj(colour, lcd_x, lcd_y, bg_priority, bg_is_0);
-        colour
+    /// Returns colour index (for DMG), or RGB555 representation (for CGB) of pixel at position (lcd_x, lcd_y),
+    /// and the DMG palette register it comes from
+    fn render_pixel(&mut self, lcd_x: usize, lcd_y: usize) -> (u16, PaletteRegister) {
+        let (colour, bg_priority, bg_is_0) = self.apply_bg(lcd_x, lcd_y);
+        self.render_obj(colour, lcd_x, lcd_y, bg_priority, bg_is_0)
     }
 
     /// Applies BG OR Window tile to current (x, y) position in LCD
@@ -274,11 +351,21 @@ impl Ppu {
         let mut x = (lcd_x + self.scx as usize) % 0xFF;
         let mut y = (lcd_y + self.scy as usize) % 0xFF;
 
+        // a hidden window still counts as drawn (for the window's line counter), with the BG shown instead
         if self.win_enabled() && self.wx_cond && self.wy_cond {
             self.line_has_window = true;
-            is_bg = false;
-            x = lcd_x + 7 - self.wx as usize;
-            y = self.win_counter;       
+            if !self.hidden_layers[Layer::Window as usize] {
+                is_bg = false;
+                x = lcd_x + 7 - self.wx as usize;
+                y = self.win_counter;
+            }
+        }
+
+        if is_bg && self.hidden_layers[Layer::Background as usize] {
+            return match self.model {
+                GBModel::DMG => (0, false, true),
+                GBModel::CGB => (Ppu::apply_palette_cgb(&0, self.cram_bg, &0), false, true),
+            };
         }
 
         let tmap_addr = (x >> 3) + ((y >> 3) << 5);
@@ -311,13 +398,18 @@ impl Ppu {
     /// Applies object tile (if any) to current (x, y) position in LCD.
     /// Decides if object covers BG based LCDC bit 0, Object's priority attribute, 
     /// BG tile's priority attribute (CGB Only) and if the BG tile has an id of 0.
-    fn render_obj(&self, bg_colour: u16, lcd_x: usize, lcd_y: usize, bg_priority: bool, bg_is_0: bool) -> u16 {
+    /// Returns the pixel's colour, and the DMG palette register it comes from.
+    fn render_obj(&self, bg_colour: u16, lcd_x: usize, lcd_y: usize, bg_priority: bool, bg_is_0: bool) -> (u16, PaletteRegister) {
         let mut colour = bg_colour;
+        let mut register = PaletteRegister::Bgp;
 
         for obj in &self.obj_buffer {
-            if !self.obj_enabled() {
+            if !self.obj_enabled() || self.hidden_layers[Layer::Objects as usize] {
                 break;
             }
+            if self.hidden_objects & (1 << obj.index) != 0 {
+                continue;
+            }
 
             if !(obj.x <= lcd_x + 8 && lcd_x < obj.x) {
                 continue;
@@ -336,6 +428,7 @@ impl Ppu {
                         if bg_is_0 || !obj.bg_priority {
                             let palette = if !obj.dmg_palette { self.obp0 } else { self.obp1 };
                             colour = Ppu::apply_palette_dmg(&id, &palette);
+                            register = obj.dmg_palette_register();
                         }
                     }
                     GBModel::CGB => {
@@ -348,7 +441,7 @@ impl Ppu {
             }
         }
 
-        colour
+        (colour, register)
     }
 
     /// Gets tile at tile_id from tile_data0 (or tile_data1 if bank = true and model is CGB) 
@@ -398,20 +491,51 @@ impl Ppu {
         let stat = self.stat & 0xFC;
         self.stat = stat | Ppu::mode_to_num(&self.mode);
         
-        if self.lyc == self.ly {
+        if self.lyc_compare_ly() == Some(self.lyc) {
             self.stat |= 0x04;
         } else {
             self.stat &= 0xFB;
         }
 
         let old_stat_line = self.stat_line;
+        self.stat_line = self.stat_sources(self.stat);
+        self.stat_triggered |= !old_stat_line && self.stat_line
+    }
 
-        self.stat_line = (self.lyc == self.ly && self.stat & 0x40 != 0) |
-            (self.mode == Mode::HBlank0 && self.stat & 0x20 != 0) |
-            (self.mode == Mode::VBlank1 && self.stat & 0x10 != 0) |
-            (self.mode == Mode::OamScan2 && self.stat & 0x08 != 0);
+    /// Returns the state of the stat line if only the sources enabled in stat were selected.
+    fn stat_sources(&self, stat: u8) -> bool {
+        // the mode 2 source is also triggered at the start of VBlank
+        let vblank_start = self.mode == Mode::VBlank1 && self.mode_elapsed_dots < 4;
 
-        self.stat_triggered = !old_stat_line && self.stat_line
+        (self.stat & 0x04 != 0 && stat & 0x40 != 0) |
+            ((self.mode == Mode::OamScan2 || vblank_start) && stat & 0x20 != 0) |
+            (self.mode == Mode::VBlank1 && stat & 0x10 != 0) |
+            (self.mode == Mode::HBlank0 && stat & 0x08 != 0)
+    }
+
+    /// Returns the LY value currently used in LY=LYC comparisons (None if comparisons are blocked).
+    fn lyc_compare_ly(&self) -> Option<u8> {
+        let line_dots = match self.mode {
+            Mode::OamScan2 => self.mode_elapsed_dots,
+            Mode::Drawing3 => MODE_2_DOTS + self.mode_elapsed_dots,
+            Mode::HBlank0 => MODE_2_DOTS + self.mode_3_dots + self.mode_elapsed_dots,
+            Mode::VBlank1 => self.mode_elapsed_dots % SCAN_LINE_DOTS,
+        };
+
+        // line 153 is compared as 153 briefly, then as 0 after LY wraps
+        if self.mode == Mode::VBlank1 && self.mode_elapsed_dots / SCAN_LINE_DOTS == 9 {
+            return match line_dots {
+                4..=7 => Some(153),
+                0..=11 => None,
+                _ => Some(0),
+            };
+        }
+
+        if line_dots < LYC_BLOCKED_DOTS && self.ly != 0 {
+            None
+        } else {
+            Some(self.ly)
+        }
     }
 
     fn lcd_ppu_disabled(&self) -> bool {
@@ -430,13 +554,60 @@ impl Ppu {
         self.lcdc & 0x02 != 0
     }
 
+    /// Returns how many objects the OAM scan keeps for a line: all of them with unlimited objects,
+    /// of which only the first LINE_OBJECTS are emulated (for timing).
+    fn line_object_limit(&self) -> usize {
+        if self.unlimited_objects { OAM_ENTRIES } else { LINE_OBJECTS }
+    }
+
     fn obj_size(&self) -> u8 {
         if self.lcdc & 0x04 == 0 { 8 } else { 16 }
     }
 
-    fn reset_lcd(&mut self) {
+    /// Turns the LCD on; the first line starts in mode 0, and the first frame is not displayed.
+    fn enable_lcd(&mut self) {
+        self.ly = 0;
+        self.mode = Mode::HBlank0;
+        self.mode_elapsed_dots = 0;
+        self.lcd_on_line = true;
+        self.skip_frame = true;
+        self.cur_pixel_x = 0;
+        self.obj_buffer = Vec::new();
+        self.wy_cond = false;
+        self.win_counter = 0;
+        self.line_has_window = false;
         self.stat_line = false;
-        self.frame_buffer = [0; LCD_BYTE_WIDTH * LCD_HEIGHT];
+    }
+
+    /// Turns the LCD off; LY and the mode are reset to 0, and a blank frame is displayed.
+    fn disable_lcd(&mut self) {
+        self.ly = 0;
+        self.mode = Mode::HBlank0;
+        self.mode_elapsed_dots = 0;
+        self.stat &= 0xFC;
+        self.stat_line = false;
+        self.clear_frame();
+        self.entered_vblank = true;
+    }
+
+    /// Fills the frame buffer with the colour of a blank LCD.
+    fn clear_frame(&mut self) {
+        let blank = match self.model {
+            GBModel::DMG => self.dmg_palette.bgp[0],
+            GBModel::CGB => [0xFF; BYTES_PER_PIXEL],
+        };
+        #[cfg(test)]
+        {
+            let blank_index = match self.model {
+                GBModel::DMG => 0,
+                GBModel::CGB => 0x7FFF,
+            };
+            self.index_buffer = [blank_index; LCD_WIDTH * LCD_HEIGHT];
+        }
+
+        for pixel in self.frame_buffer.chunks_exact_mut(BYTES_PER_PIXEL) {
+            pixel.copy_from_slice(&blank);
+        }
     }
 
     pub fn read_vram(&self, addr: usize) -> u8 {
@@ -502,11 +673,81 @@ impl Ppu {
         }
     }
 
-    fn can_access_oam(&self) -> bool {
+    /// Returns true if the CPU can access VRAM (i.e. the PPU is not drawing).
+    pub fn can_access_vram(&self) -> bool {
+        self.lcd_ppu_disabled() || self.mode != Mode::Drawing3
+    }
+
+    pub fn can_access_oam(&self) -> bool {
         self.lcd_ppu_disabled() ||
         (self.mode != Mode::Drawing3 && self.mode != Mode::OamScan2)
     }
 
+    /// (DMG ONLY) Emulates the OAM corruption bug, which corrupts the OAM row currently being
+    /// read by the PPU when the CPU accesses 0xFE00-0xFEFF during mode 2.
+    pub fn corrupt_oam(&mut self, corruption: OamCorruption) {
+        if self.lcd_ppu_disabled() || self.mode != Mode::OamScan2 {
+            return;
+        }
+
+        // PPU reads one row (8 bytes = 2 entries) of OAM every M-cycle
+        let row = (self.mode_elapsed_dots / 4) as usize;
+        if row == 0 || row >= OAM_ROWS {
+            return;
+        }
+
+        match corruption {
+            OamCorruption::Write => {
+                let a = self.oam_word(row, 0);
+                let b = self.oam_word(row - 1, 0);
+                let c = self.oam_word(row - 1, 2);
+                self.set_oam_word(row, 0, ((a ^ c) & (b ^ c)) ^ c);
+                self.copy_oam_row(row - 1, row, 1);
+            }
+            OamCorruption::Read => self.corrupt_oam_read(row),
+            OamCorruption::ReadIncrease => {
+                if (4..OAM_ROWS - 1).contains(&row) {
+                    let a = self.oam_word(row - 2, 0);
+                    let b = self.oam_word(row - 1, 0);
+                    let c = self.oam_word(row, 0);
+                    let d = self.oam_word(row - 1, 2);
+                    self.set_oam_word(row - 1, 0, (b & (a | c | d)) | (a & c & d));
+                    self.copy_oam_row(row - 1, row, 0);
+                    self.copy_oam_row(row - 1, row - 2, 0);
+                }
+                self.corrupt_oam_read(row);
+            }
+        }
+    }
+
+    fn corrupt_oam_read(&mut self, row: usize) {
+        let a = self.oam_word(row, 0);
+        let b = self.oam_word(row - 1, 0);
+        let c = self.oam_word(row - 1, 2);
+        self.set_oam_word(row, 0, b | (a & c));
+        self.copy_oam_row(row - 1, row, 1);
+    }
+
+    /// Returns the given 16-bit word (0-3) of the given OAM row (0-19).
+    fn oam_word(&self, row: usize, word: usize) -> u16 {
+        let entry = self.oam[row * 2 + word / 2];
+        let i = (word % 2) * 2;
+        u16::from_le_bytes([entry[i], entry[i + 1]])
+    }
+
+    fn set_oam_word(&mut self, row: usize, word: usize, val: u16) {
+        let entry = &mut self.oam[row * 2 + word / 2];
+        let i = (word % 2) * 2;
+        [entry[i], entry[i + 1]] = val.to_le_bytes();
+    }
+
+    /// Copies the words of OAM row src (starting from from_word) to the same words in row dest.
+    fn copy_oam_row(&mut self, src: usize, dest: usize, from_word: usize) {
+        for word in from_word..4 {
+            self.set_oam_word(dest, word, self.oam_word(src, word));
+        }
+    }
+
     pub fn read_io(&self, addr: usize) -> u8 {
         match addr {
             0xFF40 => self.lcdc,
@@ -538,11 +779,19 @@ impl Ppu {
         match addr {
             0xFF40 => {
                 if self.lcdc & 0x80 == 0 && byte & 0x80 != 0 {
-                    self.reset_lcd();   
+                    self.enable_lcd();
+                } else if self.lcdc & 0x80 != 0 && byte & 0x80 == 0 {
+                    self.disable_lcd();
                 }
                 self.lcdc = byte; 
             },
             0xFF41 => {
+                // (DMG only) writing to STAT briefly enables all of the 
+                // HBlank, VBlank and LYC sources, which can trigger an interrupt
+                if !self.is_cgb() && !self.lcd_ppu_disabled() && !self.stat_line {
+                    self.stat_write_irq = self.stat_sources(0x58);
+                }
+
                 let stat = self.stat & 0x07;
                 self.stat = (byte & 0xF8) | stat;
             },
@@ -590,7 +839,7 @@ impl Ppu {
             res += 6;
         }
 
-        for obj in &self.obj_buffer {
+        for obj in self.obj_buffer.iter().take(LINE_OBJECTS) {
             let offset = if self.win_enabled() && self.wy_cond && self.wx_cond { 
                 0xFF - self.wx 
             } else { 
@@ -616,33 +865,6 @@ impl Ppu {
         cram[index_0] as u16 | ((cram[index_1] as u16) << 8)
     }
 
-    fn rgb555_to_argb8888(colour: u16) -> [u8; BYTES_PER_PIXEL] {
-        let r5 = (colour >> 0) & 0x1F;
-        let g5 = (colour >> 5) & 0x1F;
-        let b5 = (colour >> 10) & 0x1F;
-        
-        let (blue, green, red) = if WITH_COLOUR_CORRECTION {
-            // CREDITS FOR COLOUR CORRECTION ALGORITHM:
-            // https://saveweb.github.io/near.sh/articles/video/color-emulation.html
-
-            let r = r5 * 26 + g5 * 4  + b5 * 2;
-            let g =           g5 * 24 + b5 * 8;
-            let b = r5 * 6  + g5 * 4  + b5 * 22;
-
-            let red = min(960, r) >> 2;
-            let green = min(960, g) >> 2;
-            let blue = min(960, b) >> 2;
-            (blue as u8, green as u8, red as u8)
-        } else {
-            let r8 = (r5 * 255 + 15) / 31;
-            let g8 = (g5 * 255 + 15) / 31;
-            let b8 = (b5 * 255 + 15) / 31;
-            (b8 as u8, g8 as u8, r8 as u8)
-        };
-
-        [blue, green, red, 0xFF]
-    }
-
     fn mode_to_num(mode: &Mode) -> u8 {
         match mode {
             Mode::HBlank0 => 0,
@@ -664,20 +886,331 @@ impl Ppu {
         Some(&self.frame_buffer)
     }
 
+    /// Returns the current frame as colour indices (0-3) for DMG, or RGB555 colours for CGB,
+    /// which do not depend on the DMG palette or colour correction.
+    #[cfg(test)]
+    pub fn get_index_output(&self) -> &[u16; LCD_WIDTH * LCD_HEIGHT] {
+        &self.index_buffer
+    }
+
     pub fn stat_triggered(&self) -> bool {
         self.stat_triggered
     }
 
-    pub fn entered_vblank(&self) -> bool {
-        self.entered_vblank
+    /// Returns true if VBlank was entered during the last step (for requesting interrupts).
+    pub fn vblank_triggered(&self) -> bool {
+        self.vblank_triggered
     }
 
     pub fn entered_hblank(&self) -> bool {
         self.entered_hblank
     }
+
+    pub fn in_hblank(&self) -> bool {
+        !self.lcd_ppu_disabled() && self.mode == Mode::HBlank0
+    }
+
+    pub fn lcd_enabled(&self) -> bool {
+        !self.lcd_ppu_disabled()
+    }
+
+    /// Returns the current position in the frame as (line, dot within the line), 
+    /// counting lines 144-153 through VBlank (unlike LY); (0, 0) while the LCD is off.
+    pub fn dot_position(&self) -> (u8, u16) {
+        if self.lcd_ppu_disabled() {
+            return (0, 0);
+        }
+
+        let dot = match self.mode {
+            Mode::HBlank0 if self.lcd_on_line => self.mode_elapsed_dots,
+            Mode::HBlank0 => MODE_2_DOTS + self.mode_3_dots + self.mode_elapsed_dots,
+            Mode::VBlank1 => {
+                let line = LCD_HEIGHT as u32 + self.mode_elapsed_dots / SCAN_LINE_DOTS;
+                return (line as u8, (self.mode_elapsed_dots % SCAN_LINE_DOTS) as u16);
+            }
+            Mode::OamScan2 => self.mode_elapsed_dots,
+            Mode::Drawing3 => MODE_2_DOTS + self.mode_elapsed_dots,
+        };
+        (self.ly, min(dot, SCAN_LINE_DOTS - 1) as u16)
+    }
+
+    /// Returns the current PPU mode (0-3), as in STAT.
+    pub fn mode(&self) -> u8 {
+        Ppu::mode_to_num(&self.mode)
+    }
+
+    /// Sets the display colours of DMG colour indices; the current frame keeps its colours.
+    pub fn set_dmg_palette(&mut self, palette: DmgPalette) {
+        self.dmg_palette = palette;
+        self.rgb555_palette = None;
+    }
+
+    /// Sets the display colours of DMG colour indices from CGB colours, which are converted
+    /// like CGB games' colours (again whenever the colour correction changes).
+    pub fn set_rgb555_dmg_palette(&mut self, palette: Rgb555Palette) {
+        self.dmg_palette = palette.display_palette(&self.colour_lut);
+        self.rgb555_palette = Some(palette);
+    }
+
+    #[cfg(any(test, target_arch = "wasm32"))]
+    pub fn dmg_palette(&self) -> DmgPalette {
+        self.dmg_palette
+    }
+
+    /// Sets how CGB colours are displayed (see ColourCorrection::apply), rebuilding the colour lookup table.
+    pub fn set_colour_correction(&mut self, correction: ColourCorrection, brightness: f32) {
+        self.colour_lut = ColourLut::new(correction, brightness);
+        if let Some(palette) = self.rgb555_palette {
+            self.dmg_palette = palette.display_palette(&self.colour_lut);
+        }
+    }
+
+    /// Draws every object on a line instead of only the first 10 (to avoid flicker); only the
+    /// display is affected, as mode 3 is still timed by the first 10.
+    pub fn set_unlimited_objects(&mut self, enabled: bool) {
+        self.unlimited_objects = enabled;
+    }
+}
+
+/// Layer toggles (debug API): hidden layers and objects are not drawn,
+/// without affecting any emulated state (e.g. LCDC, or mode 3 timing).
+impl Ppu {
+    pub fn set_layer_enabled(&mut self, layer: Layer, enabled: bool) {
+        self.hidden_layers[layer as usize] = !enabled;
+    }
+
+    pub fn layer_enabled(&self, layer: Layer) -> bool {
+        !self.hidden_layers[layer as usize]
+    }
+
+    /// Shows or hides the object of an OAM entry (0-39); returns false if there is no such entry.
+    pub fn set_object_enabled(&mut self, index: usize, enabled: bool) -> bool {
+        if index >= OAM_ENTRIES {
+            return false;
+        }
+
+        if enabled {
+            self.hidden_objects &= !(1 << index);
+        } else {
+            self.hidden_objects |= 1 << index;
+        }
+        true
+    }
+
+    pub fn object_enabled(&self, index: usize) -> bool {
+        index < OAM_ENTRIES && self.hidden_objects & (1 << index) == 0
+    }
+}
+
+/// An RGB image (3 bytes per pixel, row by row) rendered for debugging.
+pub struct DebugImage {
+    pub width: usize,
+    pub height: usize,
+    pub rgb: Vec<u8>,
+}
+
+impl DebugImage {
+    fn new(width: usize, height: usize) -> Self {
+        DebugImage { width, height, rgb: vec![0; width * height * 3] }
+    }
+
+    fn set_pixel(&mut self, x: usize, y: usize, rgb: [u8; 3]) {
+        let i = (y * self.width + x) * 3;
+        self.rgb[i..i + 3].copy_from_slice(&rgb);
+    }
+}
+
+// VRAM viewer images: tiles are drawn 16 per row, and palette swatches are 16x16 pixels
+const SHEET_TILES_PER_ROW: usize = 16;
+const SWATCH_SIZE: usize = 16;
+const OAM_OBJS_PER_ROW: usize = 10;
+const VIEWPORT_COLOUR: [u8; 3] = [0xFF, 0x00, 0x00];
+
+/// VRAM viewer (debug API): renders VRAM, OAM and palettes as images, using the current
+/// palettes (BGP on DMG, and BG palette 0 for tiles on CGB) and display colours.
+impl Ppu {
+    /// Returns the number of VRAM banks (2 on CGB).
+    pub fn vram_banks(&self) -> usize {
+        if matches!(self.model, GBModel::CGB) { 2 } else { 1 }
+    }
+
+    /// Renders the 384 tiles of a VRAM bank (tile_data0 or tile_data1) as a 128x192 sheet.
+    pub fn render_tile_sheet(&self, bank: usize) -> DebugImage {
+        let rows = TILE_ENTRIES / SHEET_TILES_PER_ROW;
+        let mut image = DebugImage::new(SHEET_TILES_PER_ROW * 8, rows * 8);
+
+        for tile in 0..TILE_ENTRIES {
+            for y in 0..8 {
+                for x in 0..8 {
+                    let colour_id = self.tile_colour_id(bank, tile, x, y);
+                    let colour = self.bg_colour(colour_id, 0);
+                    image.set_pixel((tile % SHEET_TILES_PER_ROW) * 8 + x, (tile / SHEET_TILES_PER_ROW) * 8 + y, colour);
+                }
+            }
+        }
+        image
+    }
+
+    /// Renders tile_map0 (map = 0) or tile_map1 (map = 1) as a 256x256 image, using the tile data
+    /// addressing mode in LCDC (and attr_map0/1 on CGB); outlines the scroll viewport if the map is used for the BG.
+    pub fn render_tile_map(&self, map: usize) -> DebugImage {
+        let (tile_map, attr_map) = if map == 0 {
+            (&self.tile_map0, &self.attr_map0)
+        } else {
+            (&self.tile_map1, &self.attr_map1)
+        };
+        let mut image = DebugImage::new(256, 256);
+
+        for y in 0..256 {
+            for x in 0..256 {
+                let tmap_addr = (x >> 3) + ((y >> 3) << 5);
+                let tile_id = tile_map[tmap_addr];
+                let colour = match self.model {
+                    GBModel::DMG => {
+                        let colour_id = self.fetch_colour_id(tile_id, false, self.lcdc & 0x10 == 0, x, y, false, false);
+                        self.bg_colour(colour_id, 0)
+                    }
+                    GBModel::CGB => {
+                        let attributes = attr_map[tmap_addr];
+                        let colour_id = self.fetch_colour_id(tile_id, attributes & 0x08 != 0, self.lcdc & 0x10 == 0,
+                            x, y, attributes & 0x20 != 0, attributes & 0x40 != 0);
+                        self.bg_colour(colour_id, attributes & 0x07)
+                    }
+                };
+                image.set_pixel(x, y, colour);
+            }
+        }
+
+        if (self.lcdc & 0x08 != 0) == (map == 1) {
+            self.outline_viewport(&mut image);
+        }
+        image
+    }
+
+    /// Outlines the 160x144 area at (SCX, SCY), wrapping around the edges of the map.
+    fn outline_viewport(&self, image: &mut DebugImage) {
+        let (left, top) = (self.scx as usize, self.scy as usize);
+        for i in 0..LCD_WIDTH {
+            let x = (left + i) % 256;
+            image.set_pixel(x, top, VIEWPORT_COLOUR);
+            image.set_pixel(x, (top + LCD_HEIGHT - 1) % 256, VIEWPORT_COLOUR);
+        }
+        for i in 0..LCD_HEIGHT {
+            let y = (top + i) % 256;
+            image.set_pixel(left, y, VIEWPORT_COLOUR);
+            image.set_pixel((left + LCD_WIDTH - 1) % 256, y, VIEWPORT_COLOUR);
+        }
+    }
+
+    /// Renders the 40 objects in OAM, 10 per row, each in an 8x16 cell (8x8 objects use the top half).
+    pub fn render_oam(&self) -> DebugImage {
+        let rows = OAM_ENTRIES / OAM_OBJS_PER_ROW;
+        let mut image = DebugImage::new(OAM_OBJS_PER_ROW * 8, rows * 16);
+
+        for (i, data) in self.oam.iter().enumerate() {
+            let obj = OAMEntry::new(i, *data);
+            let bank = (obj.cgb_use_bank_1 && matches!(self.model, GBModel::CGB)) as usize;
+            let tile_id = if self.obj_size() == 16 { obj.tile_id & 0xFE } else { obj.tile_id };
+
+            for y in 0..self.obj_size() as usize {
+                for x in 0..8 {
+                    let tile_x = if obj.x_flip { 7 - x } else { x };
+                    let tile_y = if obj.y_flip { self.obj_size() as usize - 1 - y } else { y };
+                    let colour_id = self.tile_colour_id(bank, tile_id + tile_y / 8, tile_x, tile_y % 8);
+                    let colour = self.obj_colour(colour_id, &obj);
+                    image.set_pixel((i % OAM_OBJS_PER_ROW) * 8 + x, (i / OAM_OBJS_PER_ROW) * 16 + y, colour);
+                }
+            }
+        }
+        image
+    }
+
+    /// Describes each OAM entry and its decoded attributes, one line per entry.
+    pub fn describe_oam(&self) -> Vec<String> {
+        self.oam.iter().enumerate().map(|(i, data)| {
+            let obj = OAMEntry::new(i, *data);
+            let palette = match self.model {
+                GBModel::DMG => String::from(if obj.dmg_palette { "OBP1" } else { "OBP0" }),
+                GBModel::CGB => format!("OBJ{} bank={}", obj.cgb_palette, obj.cgb_use_bank_1 as u8),
+            };
+            format!("{:02}: X={:3} Y={:3} tile=${:02X} attr=${:02X} palette={} x_flip={} y_flip={} priority={}",
+                i, obj.x, obj.y, obj.tile_id, data[3], palette, obj.x_flip as u8, obj.y_flip as u8,
+                if obj.bg_priority { "bg" } else { "obj" })
+        }).collect()
+    }
+
+    /// Renders palettes as rows of 4 swatches: BGP, OBP0 and OBP1 on DMG;
+    /// the 8 BG palettes (cram_bg) then the 8 OBJ palettes (cram_obj) on CGB.
+    pub fn render_palettes(&self) -> DebugImage {
+        let palettes: Vec<[[u8; 3]; 4]> = match self.model {
+            GBModel::DMG => [
+                (self.bgp, PaletteRegister::Bgp),
+                (self.obp0, PaletteRegister::Obp0),
+                (self.obp1, PaletteRegister::Obp1),
+            ].iter()
+                .map(|(palette, register)| [0, 1, 2, 3].map(|id| self.display_rgb(Ppu::apply_palette_dmg(&id, palette), *register)))
+                .collect(),
+            GBModel::CGB => (0..16u8)
+                .map(|i| {
+                    let cram = if i < 8 { self.cram_bg } else { self.cram_obj };
+                    [0, 1, 2, 3].map(|id| self.display_rgb(Ppu::apply_palette_cgb(&id, cram, &(i % 8)), PaletteRegister::Bgp))
+                })
+                .collect(),
+        };
+
+        let mut image = DebugImage::new(4 * SWATCH_SIZE, palettes.len() * SWATCH_SIZE);
+        for (row, palette) in palettes.iter().enumerate() {
+            for (column, colour) in palette.iter().enumerate() {
+                for y in 0..SWATCH_SIZE {
+                    for x in 0..SWATCH_SIZE {
+                        image.set_pixel(column * SWATCH_SIZE + x, row * SWATCH_SIZE + y, *colour);
+                    }
+                }
+            }
+        }
+        image
+    }
+
+    /// Returns the colour id (0-3) at (x, y) of tile (0-383, as addressed from 0x8000) in a VRAM bank.
+    fn tile_colour_id(&self, bank: usize, tile: usize, x: usize, y: usize) -> u8 {
+        let tile_data = if bank == 1 { &self.tile_data1 } else { &self.tile_data0 };
+        let tile = tile_data[tile % TILE_ENTRIES];
+        let bit = 0x80 >> x;
+        let pixel_lo = (tile[y << 1] & bit != 0) as u8;
+        let pixel_hi = (tile[(y << 1) + 1] & bit != 0) as u8;
+        (pixel_hi << 1) | pixel_lo
+    }
+
+    /// Returns the display colour of a BG colour id (through BGP on DMG, or a BG palette on CGB).
+    fn bg_colour(&self, colour_id: u8, cgb_palette: u8) -> [u8; 3] {
+        match self.model {
+            GBModel::DMG => self.display_rgb(Ppu::apply_palette_dmg(&colour_id, &self.bgp), PaletteRegister::Bgp),
+            GBModel::CGB => self.display_rgb(Ppu::apply_palette_cgb(&colour_id, self.cram_bg, &cgb_palette), PaletteRegister::Bgp),
+        }
+    }
+
+    fn obj_colour(&self, colour_id: u8, obj: &OAMEntry) -> [u8; 3] {
+        match self.model {
+            GBModel::DMG => {
+                let palette = if obj.dmg_palette { self.obp1 } else { self.obp0 };
+                self.display_rgb(Ppu::apply_palette_dmg(&colour_id, &palette), obj.dmg_palette_register())
+            }
+            GBModel::CGB => self.display_rgb(Ppu::apply_palette_cgb(&colour_id, self.cram_obj, &obj.cgb_palette), PaletteRegister::Bgp),
+        }
+    }
+
+    /// Converts a colour index (DMG, from the given palette register) or RGB555 colour (CGB) to RGB, as it is displayed.
+    fn display_rgb(&self, colour: u16, register: PaletteRegister) -> [u8; 3] {
+        let [blue, green, red, _] = match self.model {
+            GBModel::DMG => self.dmg_palette.shades(register)[colour as usize],
+            GBModel::CGB => self.colour_lut.get(colour),
+        };
+        [red, green, blue]
+    }
 }
 
 struct OAMEntry {
+    index: usize,
     y: usize,
     x: usize, 
     tile_id: usize,
@@ -690,10 +1223,11 @@ struct OAMEntry {
 }
 
 impl OAMEntry {
-    fn new(data: [u8; OAM_ENTRY_SIZE]) -> Self {
+    fn new(index: usize, data: [u8; OAM_ENTRY_SIZE]) -> Self {
         let attributes = data[3];
 
         OAMEntry {
+            index,
             y: data[0] as usize,
             x: data[1] as usize,
             tile_id: data[2] as usize,
@@ -719,61 +1253,326 @@ impl OAMEntry {
             self.tile_id
         }
     }
+
+    /// Returns the palette register the object uses on DMG.
+    fn dmg_palette_register(&self) -> PaletteRegister {
+        if self.dmg_palette { PaletteRegister::Obp1 } else { PaletteRegister::Obp0 }
+    }
 }
 
 
 #[cfg(test)]
 mod tests {
+    use std::fs::{self, File};
+    use std::io::BufWriter;
+    use std::path::Path;
+
     use crate::{cartridge::Cartridge, cpu::Cpu};
+    use crate::cpu::{test_helpers::{run_until_breakpoint, test_mooneye_rom}, GBModel, GBModel::DMG, GBModel::CGB};
+    use crate::constants::{BYTES_PER_PIXEL, LCD_HEIGHT, LCD_WIDTH};
+    use crate::config::COLOURS;
 
-    const DMG_ACID: &str = "roms/tests/dmg-acid2.gb";
+    use crate::palette::{ColourCorrection, DmgPalette, Rgb555Palette};
+    use super::{Layer, Ppu, SWATCH_SIZE};
 
-    // NOTE: DMG_CHECKHASH depends on COLOURS constant
-    const DMG_CHECKHASH: u64 = 17255647646328982741;
+    const DMG_ACID: &str = "roms/tests/dmg-acid2.gb";
+    const DMG_ACID_REFERENCE: &str = "roms/tests/dmg-acid2.png";
 
     const CGB_ACID: &str = "roms/tests/cgb-acid2.gbc";
-    const CGB_CHECKHASH: u64 = 15571388372626634589;
+    const CGB_ACID_REFERENCE: &str = "roms/tests/cgb-acid2.png";
+
+    const MEALYBUG_DIR: &str = "roms/tests/mealybug";
+    const MEALYBUG_TESTS: [&str; 8] = [
+        "m2_win_en_toggle", "m3_bgp_change", "m3_bgp_change_sprites", "m3_lcdc_bg_en_change",
+        "m3_lcdc_obj_en_change", "m3_obp0_change", "m3_scx_low_3_bits", "m3_window_timing",
+    ];
+
+    const GOLDEN_TIMEOUT: u64 = 1 << 26;
+    const GOLDEN_OUTPUT_DIR: &str = "target/golden";
 
     #[test]
     fn ppu_dmg_test() {
-        let cartridge = Cartridge::from_file(DMG_ACID, false);
-        let mut cpu = Cpu::new(cartridge, crate::cpu::GBModel::DMG);
-        let mut cycles: u32 = 0;
-        while cycles < 5000000 {
-            cycles += cpu.step() as u32;
-        } 
+        test_golden_image(DMG_ACID, DMG, DMG_ACID_REFERENCE);
+    }
+
+    #[test]
+    fn ppu_cgb_test() {
+        test_golden_image(CGB_ACID, CGB, CGB_ACID_REFERENCE);
+    }
+
+    #[test]
+    fn ppu_mealybug_test() {
+        for test in MEALYBUG_TESTS {
+            let rom = format!("{}/{}.gb", MEALYBUG_DIR, test);
+            let reference = format!("{}/expected/DMG-blob/{}.png", MEALYBUG_DIR, test);
+            test_golden_image(&rom, DMG, &reference);
+        }
+    }
+
+    #[test]
+    fn ppu_lcd_on_test() {
+        test_mooneye_rom("roms/tests/mooneye/acceptance/ppu/lcdon_timing-GS.gb", DMG);
+        test_mooneye_rom("roms/tests/mooneye/acceptance/ppu/lcdon_write_timing-GS.gb", DMG);
+    }
 
-        let hash = match cpu.get_display_output() {
-            Some(frame_buffer) => fnv1a(frame_buffer),
-            None => 0
+    #[test]
+    fn ppu_stat_test() {
+        test_mooneye_rom("roms/tests/mooneye/acceptance/ppu/stat_irq_blocking.gb", DMG);
+        test_mooneye_rom("roms/tests/mooneye/acceptance/ppu/stat_lyc_onoff.gb", DMG);
+        test_mooneye_rom("roms/tests/mooneye/acceptance/ppu/vblank_stat_intr-GS.gb", DMG);
+        test_mooneye_rom("roms/tests/mooneye/acceptance/ppu/intr_1_2_timing-GS.gb", DMG);
+        test_mooneye_rom("roms/tests/mooneye/acceptance/ppu/intr_2_0_timing.gb", DMG);
+    }
+
+    /// Runs the ROM until it reaches LD B,B (or GOLDEN_TIMEOUT), then compares the next complete
+    /// frame to the reference PNG by colour index (for DMG) or RGB555 colour (for CGB).
+    /// On failure, the actual frame and a diff (mismatches in red) are written to GOLDEN_OUTPUT_DIR.
+    fn test_golden_image(rom_path: &str, model: GBModel, reference_path: &str) {
+        let cartridge = Cartridge::from_file(rom_path, false);
+        let mut cpu = Cpu::new(cartridge, model);
+
+        run_until_breakpoint(&mut cpu, GOLDEN_TIMEOUT);
+
+        // wait for the next complete frame
+        cpu.get_display_output();
+        let mut cycles = 0;
+        let actual_rgb: Vec<u8> = loop {
+            if let Some(frame) = cpu.get_display_output() {
+                break frame.chunks_exact(BYTES_PER_PIXEL).flat_map(|bgra| [bgra[2], bgra[1], bgra[0]]).collect();
+            }
+            assert!(cycles < 4 * 70224, "{}: no frame was displayed", rom_path);
+            cycles += cpu.step();
         };
 
-        assert!(hash == DMG_CHECKHASH, "hash mismatch: got {} but expected {}", hash, DMG_CHECKHASH);
+        let reference = load_png_rgb(reference_path);
+        let actual = cpu.get_index_output();
+
+        let mut mismatches = 0;
+        let mut diff = Vec::with_capacity(LCD_WIDTH * LCD_HEIGHT * 3);
+        for (i, rgb) in reference.iter().enumerate() {
+            let expected = match model {
+                GBModel::DMG => rgb_to_dmg_index(*rgb),
+                GBModel::CGB => rgb_to_rgb555(*rgb),
+            };
+
+            if actual[i] == expected {
+                // faded reference pixel
+                diff.extend(rgb.map(|c| 0xC0 + c / 4));
+            } else {
+                mismatches += 1;
+                diff.extend([0xFF, 0x00, 0x00]);
+            }
+        }
+
+        if mismatches == 0 {
+            return;
+        }
+
+        let name = Path::new(reference_path).file_stem().unwrap().to_string_lossy();
+
+        fs::create_dir_all(GOLDEN_OUTPUT_DIR).unwrap();
+        let actual_path = format!("{}/{}-actual.png", GOLDEN_OUTPUT_DIR, name);
+        let diff_path = format!("{}/{}-diff.png", GOLDEN_OUTPUT_DIR, name);
+        save_png_rgb(&actual_path, &actual_rgb);
+        save_png_rgb(&diff_path, &diff);
+
+        panic!("{}: {} pixels differ from {} (see {})", rom_path, mismatches, reference_path, diff_path);
     }
 
     #[test]
-    fn ppu_cgb_test() {
-        let cartridge = Cartridge::from_file(CGB_ACID, false);
-        let mut cpu = Cpu::new(cartridge, crate::cpu::GBModel::CGB);
-        let mut cycles: u32 = 0;
-        while cycles < 5000000 {
-            cycles += cpu.step() as u32;
-        } 
-        
-        let hash = match cpu.get_display_output() {
-            Some(frame_buffer) => fnv1a(frame_buffer),
-            None => 0
+    fn vram_viewer_test() {
+        let mut ppu = Ppu::new(DMG);
+        ppu.write_io(0xFF40, 0x11);
+        ppu.write_io(0xFF47, 0xE4);
+        ppu.write_vram(0x8010, 0xFF);
+        ppu.write_vram(0x8012, 0xFF);
+        ppu.write_vram(0x9800, 0x01);
+        let rgb = |id: usize| [COLOURS[id][2], COLOURS[id][1], COLOURS[id][0]];
+
+        let sheet = ppu.render_tile_sheet(0);
+        assert_eq!((sheet.width, sheet.height), (128, 192));
+        assert_eq!(sheet.rgb[8 * 3..8 * 3 + 3], rgb(1));
+        assert_eq!(sheet.rgb[9 * 3..9 * 3 + 3], rgb(1));
+        assert_eq!(sheet.rgb[(2 * 128 + 8) * 3..(2 * 128 + 8) * 3 + 3], rgb(0));
+
+        // map 0 is used for the BG, so the viewport at (SCX, SCY) = (0, 0) is outlined
+        let map = ppu.render_tile_map(0);
+        assert_eq!((map.width, map.height), (256, 256));
+        assert_eq!(map.rgb[0..3], [0xFF, 0x00, 0x00]);
+        assert_eq!(map.rgb[(256 + 1) * 3..(256 + 1) * 3 + 3], rgb(1));
+        assert_eq!(map.rgb[(2 * 256 + 1) * 3..(2 * 256 + 1) * 3 + 3], rgb(0));
+
+        assert_eq!(ppu.describe_oam().len(), 40);
+        assert_eq!(ppu.render_palettes().height, 3 * SWATCH_SIZE);
+
+        // OBP0 (the second row of swatches) with its own shades
+        ppu.write_io(0xFF48, 0xE4);
+        let mut palette = DmgPalette::uniform(COLOURS);
+        palette.obp0 = DmgPalette::preset("contrast").unwrap().obp0;
+        ppu.set_dmg_palette(palette);
+        let palettes = ppu.render_palettes();
+        let pixel = |x: usize, y: usize| palettes.rgb[(y * palettes.width + x) * 3..(y * palettes.width + x) * 3 + 3].to_vec();
+        assert_eq!(pixel(3 * SWATCH_SIZE, 0), rgb(3));
+        assert_eq!(pixel(3 * SWATCH_SIZE, SWATCH_SIZE), [0x00, 0x00, 0x00]);
+        assert_eq!(pixel(0, SWATCH_SIZE), [0xFF, 0xFF, 0xFF]);
+    }
+
+    #[test]
+    fn rgb555_dmg_palette_test() {
+        let mut ppu = Ppu::new(DMG);
+        let palette = Rgb555Palette { bgp: [0x7FFF, 0x421F, 0x1CF2, 0x0000], obp0: [0x7FFF; 4], obp1: [0x0000; 4] };
+        ppu.set_rgb555_dmg_palette(palette);
+        assert_eq!(ppu.dmg_palette().bgp[1], ColourCorrection::default().apply(0x421F, 0.0));
+
+        // changing the colour correction or brightness converts the palette again
+        ppu.set_colour_correction(ColourCorrection::None, 0.5);
+        assert_eq!(ppu.dmg_palette().bgp[1], ColourCorrection::None.apply(0x421F, 0.5));
+        assert_eq!(ppu.dmg_palette().obp0[0], [0xFF, 0xFF, 0xFF, 0xFF]);
+
+        // but not palettes of display colours
+        ppu.set_dmg_palette(DmgPalette::uniform(COLOURS));
+        ppu.set_colour_correction(ColourCorrection::Cgb, 0.0);
+        assert_eq!(ppu.dmg_palette(), DmgPalette::uniform(COLOURS));
+    }
+
+    #[test]
+    fn unlimited_objects_test() {
+        // 12 objects of solid colour 3 side by side on lines 1-8, from x = 0
+        let run_line = |unlimited: bool| {
+            let mut ppu = Ppu::new(DMG);
+            ppu.set_unlimited_objects(unlimited);
+            ppu.write_io(0xFF47, 0xE4);
+            ppu.write_io(0xFF48, 0xE4);
+            for i in 0..16 {
+                ppu.write_vram(0x8010 + i, 0xFF);
+            }
+            for i in 0..12 {
+                let entry = [17, 8 + 8 * i as u8, 1, 0];
+                for (j, byte) in entry.iter().enumerate() {
+                    ppu.write_oam(0xFE00 + i * 4 + j, *byte);
+                }
+            }
+
+            ppu.write_io(0xFF40, 0x83);
+            let mut mode_3_dots = 0;
+            while ppu.dot_position().0 < 2 {
+                ppu.step(4);
+                if ppu.mode() == 3 && ppu.ly == 1 {
+                    mode_3_dots = ppu.mode_3_dots;
+                }
+            }
+            let line: Vec<u16> = ppu.index_buffer[LCD_WIDTH..2 * LCD_WIDTH].to_vec();
+            (mode_3_dots, line)
         };
 
-        assert!(hash == CGB_CHECKHASH, "hash mismatch: got {} but expected {}", hash, CGB_CHECKHASH);
+        let (limited_dots, limited) = run_line(false);
+        let (unlimited_dots, unlimited) = run_line(true);
+        assert_eq!(limited_dots, unlimited_dots);
+        assert_eq!(limited[79], 3);
+        assert_eq!(limited[85], 0);
+        assert_eq!(unlimited[85], 3);
+        assert_eq!(unlimited[95], 3);
+        assert_eq!(unlimited[96], 0);
     }
 
-    fn fnv1a(bytes: &[u8]) -> u64 {
-        let mut hash = 0xcbf29ce484222325;
-        for byte in bytes {
-            hash = hash ^ (*byte as u64);
-            hash = hash.wrapping_mul(0x100000001b3);
-        }
-        hash
+    #[test]
+    fn layer_toggle_test() {
+        let mut ppu = Ppu::new(DMG);
+        ppu.write_io(0xFF40, 0xE3);
+        ppu.set_layer_enabled(Layer::Window, false);
+        ppu.set_object_enabled(12, false);
+
+        assert!(ppu.layer_enabled(Layer::Background));
+        assert!(!ppu.layer_enabled(Layer::Window));
+        assert!(!ppu.object_enabled(12));
+        assert!(ppu.object_enabled(13));
+        // the renderer's toggles are not visible to the emulated game
+        assert_eq!(ppu.read_io(0xFF40), 0xE3);
+
+        ppu.set_layer_enabled(Layer::Window, true);
+        ppu.set_object_enabled(12, true);
+        assert!(ppu.layer_enabled(Layer::Window));
+        assert!(ppu.object_enabled(12));
+
+        // there are only 40 OAM entries
+        assert!(!ppu.set_object_enabled(40, false));
+        assert!(!ppu.set_object_enabled(64, false));
+        assert!(!ppu.object_enabled(40));
+
+        // BG of colour 1, window of colour 2 from x = 80, and objects of colour 3 at x = 0, 8 and 96
+        let run_line = |hide: &dyn Fn(&mut Ppu)| {
+            let mut ppu = Ppu::new(DMG);
+            ppu.write_io(0xFF47, 0xE4);
+            ppu.write_io(0xFF48, 0xE4);
+            ppu.write_io(0xFF4A, 0);
+            ppu.write_io(0xFF4B, 87);
+            for i in 0..8 {
+                ppu.write_vram(0x8010 + i * 2, 0xFF);
+                ppu.write_vram(0x8020 + i * 2 + 1, 0xFF);
+                ppu.write_vram(0x8030 + i * 2, 0xFF);
+                ppu.write_vram(0x8030 + i * 2 + 1, 0xFF);
+            }
+            for i in 0..0x400 {
+                ppu.write_vram(0x9800 + i, 1);
+                ppu.write_vram(0x9C00 + i, 2);
+            }
+            for (i, x) in [8, 16, 104].iter().enumerate() {
+                for (j, byte) in [17, *x, 3, 0].iter().enumerate() {
+                    ppu.write_oam(0xFE00 + i * 4 + j, *byte);
+                }
+            }
+            hide(&mut ppu);
+
+            ppu.write_io(0xFF40, 0xF3);
+            while ppu.dot_position().0 < 2 {
+                ppu.step(4);
+            }
+            // pixels of line 1 in: object 0, object 1, BG, window, object 2 (over the window)
+            [0, 10, 20, 85, 100].map(|x| ppu.index_buffer[LCD_WIDTH + x])
+        };
+
+        assert_eq!(run_line(&|_| {}), [3, 3, 1, 2, 3]);
+        assert_eq!(run_line(&|ppu| ppu.set_layer_enabled(Layer::Background, false)), [3, 3, 0, 2, 3]);
+        // a hidden window shows the BG instead
+        assert_eq!(run_line(&|ppu| ppu.set_layer_enabled(Layer::Window, false)), [3, 3, 1, 1, 3]);
+        assert_eq!(run_line(&|ppu| ppu.set_layer_enabled(Layer::Objects, false)), [1, 1, 1, 2, 2]);
+        assert_eq!(run_line(&|ppu| { ppu.set_object_enabled(1, false); }), [3, 1, 1, 2, 3]);
+    }
+
+    /// Loads a 160x144 PNG as RGB pixels.
+    fn load_png_rgb(path: &str) -> Vec<[u8; 3]> {
+        let file = File::open(path).unwrap_or_else(|e| panic!("Error opening {}: {}", path, e));
+        let mut decoder = png::Decoder::new(file);
+        decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+
+        let mut reader = decoder.read_info().unwrap();
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buf).unwrap();
+        assert!(info.width as usize == LCD_WIDTH && info.height as usize == LCD_HEIGHT, 
+            "{} is not {}x{}", path, LCD_WIDTH, LCD_HEIGHT);
+
+        let samples = info.color_type.samples();
+        buf[..info.buffer_size()].chunks_exact(samples).map(|pixel| match info.color_type {
+            png::ColorType::Grayscale | png::ColorType::GrayscaleAlpha => [pixel[0]; 3],
+            _ => [pixel[0], pixel[1], pixel[2]],
+        }).collect()
+    }
+
+    fn save_png_rgb(path: &str, rgb: &[u8]) {
+        let file = BufWriter::new(File::create(path).unwrap());
+        let mut encoder = png::Encoder::new(file, LCD_WIDTH as u32, LCD_HEIGHT as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header().unwrap().write_image_data(rgb).unwrap();
+    }
+
+    /// Maps a reference colour to a DMG colour index (0 = lightest, 3 = darkest) by its brightness.
+    fn rgb_to_dmg_index(rgb: [u8; 3]) -> u16 {
+        let luma = (rgb[0] as u32 * 299 + rgb[1] as u32 * 587 + rgb[2] as u32 * 114) / 1000;
+        3 - ((luma * 3 + 127) / 255) as u16
+    }
+
+    fn rgb_to_rgb555(rgb: [u8; 3]) -> u16 {
+        (rgb[0] as u16 >> 3) | ((rgb[1] as u16 >> 3) << 5) | ((rgb[2] as u16 >> 3) << 10)
     }
-}
\ No newline at end of file
+}
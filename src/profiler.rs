@@ -0,0 +1,262 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::rc::Rc;
+
+use crate::cpu::{Cpu, MemoryBus};
+use crate::disasm::{disassemble, format_addr};
+use crate::symbols::Symbols;
+
+// number of frames between hot spot reports
+const REPORT_FRAMES: u64 = 60;
+// number of entries in hot spot reports
+const REPORT_ENTRIES: usize = 5;
+const SESSION_REPORT_ENTRIES: usize = 20;
+// call stacks deeper than this are assumed to be lost track of (e.g. after SP was reloaded)
+const MAX_STACK_DEPTH: usize = 64;
+
+const INTERRUPT_VECTORS: [u16; 5] = [0x40, 0x48, 0x50, 0x58, 0x60];
+
+struct Frame {
+    name: String,
+    // SP right after the return address was pushed; the frame returns once SP is above it
+    entry_sp: u16,
+}
+
+/// State of the CPU before a step.
+#[derive(Default)]
+struct Before {
+    pc: u16,
+    bank: usize,
+    sp: u16,
+    halted: bool,
+    call_target: Option<u16>,
+}
+
+/// Attributes T-cycles to the (bank, PC) of each instruction, and to functions (by their labels,
+/// or otherwise by following calls and returns); reports hot spots every REPORT_FRAMES frames
+/// and for the whole session, and writes folded stacks (as used by flamegraph tools).
+pub struct Profiler {
+    output_path: String,
+    symbols: Option<Rc<Symbols>>,
+    stack: Vec<Frame>,
+    before: Before,
+    by_addr: HashMap<(usize, u16), u64>,
+    by_function: HashMap<String, u64>,
+    window_by_function: HashMap<String, u64>,
+    folded: HashMap<String, u64>,
+    total_cycles: u64,
+    window_cycles: u64,
+    frames: u64,
+}
+
+impl Profiler {
+    /// Makes a profiler which writes folded stacks to output_path when finished.
+    pub fn new(output_path: &str, symbols: Option<Rc<Symbols>>) -> Self {
+        Profiler {
+            output_path: output_path.to_string(),
+            symbols,
+            stack: Vec::new(),
+            before: Before::default(),
+            by_addr: HashMap::new(),
+            by_function: HashMap::new(),
+            window_by_function: HashMap::new(),
+            folded: HashMap::new(),
+            total_cycles: 0,
+            window_cycles: 0,
+            frames: 0,
+        }
+    }
+
+    /// Records the CPU's state before it steps.
+    pub fn before_step<B: MemoryBus>(&mut self, cpu: &Cpu<B>) {
+        let registers = cpu.registers();
+        let bank = cpu.rom_bank();
+        let call_target = match cpu.peek_byte(registers.pc) {
+            // CALL (conditional or not) and RST
+            0xCD | 0xC4 | 0xCC | 0xD4 | 0xDC | 0xC7 | 0xCF | 0xD7 | 0xDF | 0xE7 | 0xEF | 0xF7 | 0xFF => {
+                disassemble(|addr| cpu.peek_byte(addr), registers.pc, bank, None).target
+            }
+            _ => None,
+        };
+
+        self.before = Before { pc: registers.pc, bank, sp: registers.sp, halted: registers.halted, call_target };
+    }
+
+    /// Attributes the step's T-cycles, then follows any call, interrupt or return made by it.
+    pub fn after_step<B: MemoryBus>(&mut self, cpu: &Cpu<B>, t_cycles: u32) {
+        let t_cycles = t_cycles as u64;
+        let leaf = self.current_function();
+        let mut stack: Vec<&str> = self.stack.iter().map(|frame| frame.name.as_str()).collect();
+        if stack.last() != Some(&leaf.as_str()) {
+            stack.push(&leaf);
+        }
+
+        *self.folded.entry(stack.join(";")).or_insert(0) += t_cycles;
+        let before = &self.before;
+        *self.by_addr.entry((bank_of(before.pc, before.bank), before.pc)).or_insert(0) += t_cycles;
+        *self.by_function.entry(leaf.clone()).or_insert(0) += t_cycles;
+        *self.window_by_function.entry(leaf).or_insert(0) += t_cycles;
+        self.total_cycles += t_cycles;
+        self.window_cycles += t_cycles;
+
+        self.follow_calls(cpu);
+    }
+
+    fn follow_calls<B: MemoryBus>(&mut self, cpu: &Cpu<B>) {
+        let registers = cpu.registers();
+        let bank = cpu.rom_bank();
+        let before_sp = self.before.sp;
+
+        while self.stack.last().is_some_and(|frame| registers.sp > frame.entry_sp) {
+            self.stack.pop();
+        }
+
+        // a call pushes the return address; an interrupt dispatched in the same step pushes another
+        let mut sp = before_sp;
+        if let Some(target) = self.before.call_target {
+            if registers.sp <= before_sp.wrapping_sub(2) {
+                sp = before_sp.wrapping_sub(2);
+                let name = self.function_of(target, bank);
+                self.push_frame(name, sp);
+            }
+        }
+        if INTERRUPT_VECTORS.contains(&registers.pc) && registers.sp == sp.wrapping_sub(2) {
+            let name = self.function_of(registers.pc, bank);
+            self.push_frame(name, registers.sp);
+        }
+    }
+
+    fn push_frame(&mut self, name: String, entry_sp: u16) {
+        if self.stack.len() == MAX_STACK_DEPTH {
+            self.stack.clear();
+        }
+        self.stack.push(Frame { name, entry_sp });
+    }
+
+    /// Returns the function at addr: its nearest global label, or the address itself without symbols.
+    fn function_of(&self, addr: u16, bank: usize) -> String {
+        match self.symbols.as_ref().and_then(|symbols| symbols.nearest(addr, bank)) {
+            Some((label, _)) => label.split('.').next().unwrap().to_string(),
+            None => format_addr(addr, bank).trim().to_string(),
+        }
+    }
+
+    /// Returns the function the instruction before the step belongs to: by its label with symbols,
+    /// or otherwise the function last called.
+    fn current_function(&self) -> String {
+        if self.before.halted {
+            return String::from("(halted)");
+        }
+
+        match (&self.symbols, self.stack.last()) {
+            (Some(_), _) => self.function_of(self.before.pc, self.before.bank),
+            (None, Some(frame)) => frame.name.clone(),
+            (None, None) => String::from("(root)"),
+        }
+    }
+
+    /// Called at the end of each frame; prints hot spots every REPORT_FRAMES frames.
+    pub fn end_frame(&mut self) {
+        self.frames += 1;
+        if self.frames % REPORT_FRAMES != 0 {
+            return;
+        }
+
+        println!("Profile of frames {}-{} (average T-cycles per frame):", self.frames - REPORT_FRAMES + 1, self.frames);
+        print_top(&self.window_by_function, self.window_cycles, REPORT_FRAMES, REPORT_ENTRIES);
+        self.window_by_function.clear();
+        self.window_cycles = 0;
+    }
+
+    /// Prints the session's hot spots, and writes the folded stacks.
+    pub fn finish(&self) {
+        let frames = self.frames.max(1);
+        println!("Session profile over {} frames (average T-cycles per frame):", self.frames);
+        print_top(&self.by_function, self.total_cycles, frames, SESSION_REPORT_ENTRIES);
+
+        println!("Hottest instructions:");
+        let by_addr: HashMap<String, u64> = self.by_addr.iter()
+            .map(|((bank, addr), cycles)| (format_addr(*addr, *bank).trim().to_string(), *cycles))
+            .collect();
+        print_top(&by_addr, self.total_cycles, frames, SESSION_REPORT_ENTRIES);
+
+        match self.write_folded() {
+            Ok(()) => println!("Wrote folded stacks to {}", self.output_path),
+            Err(e) => println!("Could not write folded stacks to {}: {}", self.output_path, e),
+        }
+    }
+
+    fn write_folded(&self) -> io::Result<()> {
+        let mut file = BufWriter::new(File::create(&self.output_path)?);
+        for line in self.folded_lines() {
+            writeln!(file, "{}", line)?;
+        }
+        file.flush()
+    }
+
+    /// Returns one line per call stack (sorted): functions separated by ';', then the T-cycles spent in it.
+    fn folded_lines(&self) -> Vec<String> {
+        let mut stacks: Vec<(&String, &u64)> = self.folded.iter().collect();
+        stacks.sort();
+        stacks.into_iter().map(|(stack, cycles)| format!("{} {}", stack, cycles)).collect()
+    }
+}
+
+/// Returns the bank to key addr by: bank for 0x4000-0x7FFF, otherwise 0.
+fn bank_of(addr: u16, bank: usize) -> usize {
+    if (0x4000..=0x7FFF).contains(&addr) { bank } else { 0 }
+}
+
+fn print_top(cycles: &HashMap<String, u64>, total: u64, frames: u64, count: usize) {
+    let mut entries: Vec<(&String, &u64)> = cycles.iter().collect();
+    entries.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+
+    for (name, cycles) in entries.into_iter().take(count) {
+        let percent = 100.0 * *cycles as f64 / total.max(1) as f64;
+        println!("  {:>10}  {:>5.1}%  {}", cycles / frames, percent, name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Profiler;
+    use crate::cpu::{Cpu, FlatBus, GBModel};
+
+    #[test]
+    fn profiler_test() {
+        let mut bus = FlatBus::new();
+        // CALL $0200, NOP
+        bus.load(0x0100, &[0xCD, 0x00, 0x02, 0x00]);
+        // RST $08, EI, NOP (the VBlank interrupt is dispatched after it), RET
+        bus.load(0x0200, &[0xCF, 0xFB, 0x00, 0xC9]);
+        // RET
+        bus.load(0x0008, &[0xC9]);
+        // RETI
+        bus.load(0x0040, &[0xD9]);
+        bus.load(0xFF0F, &[0x01]);
+        bus.load(0xFFFF, &[0x01]);
+
+        let mut cpu = Cpu::from_bus(bus, GBModel::DMG);
+        let mut profiler = Profiler::new("", None);
+        for _ in 0..8 {
+            profiler.before_step(&cpu);
+            let t_cycles = cpu.step();
+            profiler.after_step(&cpu, t_cycles);
+        }
+        assert_eq!(cpu.registers().pc, 0x0104);
+        assert!(profiler.stack.is_empty());
+
+        assert_eq!(profiler.folded_lines(), [
+            "(root) 28",
+            "00:0200 60",
+            "00:0200;00:0008 16",
+            "00:0200;00:0040 16",
+        ]);
+        assert_eq!(profiler.by_function["(root)"], 28);
+        assert_eq!(profiler.by_function["00:0200"], 60);
+        assert_eq!(profiler.by_function["00:0008"], 16);
+        assert_eq!(profiler.by_function["00:0040"], 16);
+        assert_eq!(profiler.total_cycles, 120);
+    }
+}
@@ -0,0 +1,138 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Labels loaded from an RGBDS symbol file (lines of 'bank:addr label', in hex).
+/// NOTE: banks are only told apart for ROM (0x4000-0x7FFF); for other addresses
+/// (e.g. WRAMX) the first label at an address is used.
+#[derive(Default)]
+pub struct Symbols {
+    by_addr: BTreeMap<(usize, u16), String>,
+    by_name: HashMap<String, (usize, u16)>,
+}
+
+/// Bank used as a key for addr, where bank is the ROM bank mapped to 0x4000-0x7FFF.
+fn key(addr: u16, bank: usize) -> (usize, u16) {
+    match addr {
+        0x4000..=0x7FFF => (bank, addr),
+        _ => (0, addr),
+    }
+}
+
+/// Memory region of addr; nearest labels are only looked up within the same region.
+fn region(addr: u16) -> u8 {
+    match addr {
+        0x0000..=0x3FFF => 0,
+        0x4000..=0x7FFF => 1,
+        0x8000..=0x9FFF => 2,
+        0xA000..=0xBFFF => 3,
+        0xC000..=0xCFFF => 4,
+        0xD000..=0xDFFF => 5,
+        _ => 6,
+    }
+}
+
+impl Symbols {
+    /// Loads the symbol file next to the given ROM (e.g. game.sym for game.gb), if there is one.
+    pub fn for_rom(rom_path: &str) -> Option<io::Result<Self>> {
+        let path = Path::new(rom_path).with_extension("sym");
+        if path.exists() {
+            Some(fs::read_to_string(&path).map(|text| Symbols::parse(&text)))
+        } else {
+            None
+        }
+    }
+
+    /// Parses the contents of a symbol file; comments (;) and invalid lines are skipped.
+    pub fn parse(text: &str) -> Self {
+        let mut symbols = Symbols::default();
+
+        for line in text.lines() {
+            let line = line.split(';').next().unwrap().trim();
+            let (location, name) = match line.split_once(char::is_whitespace) {
+                Some((location, name)) => (location, name.trim()),
+                None => continue,
+            };
+            let (bank, addr) = match location.split_once(':') {
+                Some((bank, addr)) => (usize::from_str_radix(bank, 16), u16::from_str_radix(addr, 16)),
+                None => continue,
+            };
+
+            if let (Ok(bank), Ok(addr)) = (bank, addr) {
+                symbols.by_addr.entry(key(addr, bank)).or_insert_with(|| name.to_string());
+                symbols.by_name.insert(name.to_string(), (bank, addr));
+            }
+        }
+
+        symbols
+    }
+
+    pub fn len(&self) -> usize {
+        self.by_name.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_name.is_empty()
+    }
+
+    /// Returns the label at addr, with bank mapped to 0x4000-0x7FFF.
+    pub fn label(&self, addr: u16, bank: usize) -> Option<&str> {
+        self.by_addr.get(&key(addr, bank)).map(String::as_str)
+    }
+
+    /// Returns the bank and address of a label.
+    pub fn lookup(&self, name: &str) -> Option<(usize, u16)> {
+        self.by_name.get(name).copied()
+    }
+
+    /// Returns the closest label at or before addr (in the same bank and region), and the offset from it.
+    pub fn nearest(&self, addr: u16, bank: usize) -> Option<(&str, u16)> {
+        let key = key(addr, bank);
+        let ((label_bank, label_addr), name) = self.by_addr.range(..=key).next_back()?;
+        if *label_bank != key.0 || region(*label_addr) != region(addr) {
+            return None;
+        }
+        Some((name, addr - label_addr))
+    }
+
+    /// Describes addr as its closest label, e.g. PlayerUpdate+$12.
+    pub fn describe(&self, addr: u16, bank: usize) -> Option<String> {
+        match self.nearest(addr, bank)? {
+            (name, 0) => Some(name.to_string()),
+            (name, offset) => Some(format!("{}+${:X}", name, offset)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Symbols;
+
+    const SYM: &str = "\
+; File generated by rgblink
+00:0150 Main
+00:0158 Main.loop
+03:4000 PlayerUpdate
+04:4000 EnemyUpdate
+00:c000 wPlayerX
+";
+
+    #[test]
+    fn symbols_test() {
+        let symbols = Symbols::parse(SYM);
+        assert_eq!(symbols.len(), 5);
+
+        assert_eq!(symbols.label(0x0150, 1), Some("Main"));
+        assert_eq!(symbols.label(0x4000, 3), Some("PlayerUpdate"));
+        assert_eq!(symbols.label(0x4000, 4), Some("EnemyUpdate"));
+        assert_eq!(symbols.label(0x4000, 5), None);
+        assert_eq!(symbols.lookup("EnemyUpdate"), Some((4, 0x4000)));
+
+        assert_eq!(symbols.describe(0x015A, 3), Some(String::from("Main.loop+$2")));
+        assert_eq!(symbols.describe(0x4012, 3), Some(String::from("PlayerUpdate+$12")));
+        assert_eq!(symbols.describe(0x4012, 2), None);
+        assert_eq!(symbols.describe(0xC000, 1), Some(String::from("wPlayerX")));
+        assert_eq!(symbols.describe(0x8000, 1), None);
+    }
+}
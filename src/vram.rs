@@ -0,0 +1,29 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::image::save_png_rgb;
+use crate::ppu::{DebugImage, Ppu};
+
+/// Saves the VRAM viewer's images into dir (created if needed): the tile sheet of each VRAM bank
+/// (tiles0.png, and tiles1.png on CGB), both tile maps (map0.png, map1.png), OAM (oam.png,
+/// with its decoded attributes in oam.txt) and the palettes (palettes.png).
+pub fn dump(ppu: &Ppu, dir: &str) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    let save = |name: &str, image: DebugImage| {
+        let path = Path::new(dir).join(name);
+        save_png_rgb(&path.to_string_lossy(), image.width, image.height, &image.rgb)
+    };
+
+    for bank in 0..ppu.vram_banks() {
+        save(&format!("tiles{}.png", bank), ppu.render_tile_sheet(bank))?;
+    }
+    save("map0.png", ppu.render_tile_map(0))?;
+    save("map1.png", ppu.render_tile_map(1))?;
+    save("oam.png", ppu.render_oam())?;
+    save("palettes.png", ppu.render_palettes())?;
+
+    let mut oam = ppu.describe_oam().join("\n");
+    oam.push('\n');
+    fs::write(Path::new(dir).join("oam.txt"), oam)
+}